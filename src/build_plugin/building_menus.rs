@@ -1,8 +1,41 @@
-use bevy::prelude::*;
+use bevy::{
+    ecs::{
+        query::{QueryData, QueryFilter},
+        relationship::RelationshipTarget,
+    },
+    platform::collections::HashMap,
+    prelude::*,
+};
 
-use crate::{FontAssets, GameState, resources_plugin::Inventory, ui_state::InMenu};
+use crate::{
+    FontAssets, GameState, InGameState,
+    combat_plugin::Health,
+    crew_plugin::{
+        CrewMember, MoveIn, Occupants, Rehouse, Workers, WorksAt, beds, can_hire, staffed,
+    },
+    resources_plugin::{
+        AcceptedItems, Capacity, Holds, Inventory, Item, ItemRegistry, Items, can_afford,
+        spawn_items, store_what_fits, take_items, total_owned,
+    },
+    train_plugin::{Locomotive, TrainCar},
+    ui_state::InMenu,
+    world_plugin::{
+        CurrentStop,
+        stop_plugin::{ActiveContracts, Contract},
+    },
+};
 
-use super::Building;
+use super::{
+    Building, BuildingType, Relocating, ResourceProduction, RestoreBuildLocation,
+    definitions::Buildings, format_cost, tiers::BuildingTier, turrets::Turret,
+};
+
+const TRANSFER_BUTTON_COLOR: Color = Color::srgb(0.85, 0.85, 0.85);
+const SELECTED_AMOUNT_COLOR: Color = Color::srgb(0.6, 0.8, 0.6);
+const UNAVAILABLE_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
+const LOW_MORALE_COLOR: Color = Color::srgb(0.8, 0., 0.);
+/// Crew below this morale are shown as unhappy.
+const LOW_MORALE: f32 = 0.5;
 
 pub fn building_menus_plugin(app: &mut App) {
     app.add_systems(OnEnter(GameState::InGame), spawn_building_menu)
@@ -11,10 +44,42 @@ pub fn building_menus_plugin(app: &mut App) {
         .add_systems(
             FixedUpdate,
             update_inspected_building.run_if(
-                resource_exists::<BuildingInspected>.and(resource_changed::<BuildingInspected>),
+                resource_exists::<BuildingInspected>.and(
+                    resource_changed::<BuildingInspected>
+                        .or(resource_changed::<Transfer>)
+                        .or(resource_changed::<ActiveContracts>),
+                ),
             ),
         )
-        .insert_resource(BuildingInspected(None));
+        .add_systems(
+            Update,
+            (
+                update_production_status,
+                update_turret_status,
+                (
+                    building_actions,
+                    crew_buttons,
+                    transfer_target_button,
+                    transfer_amount_buttons,
+                    transfer_buttons,
+                )
+                    .run_if(in_state(InGameState::Running)),
+            )
+                .run_if(in_state(InMenu::BuildingMenu)),
+        )
+        .add_systems(
+            OnExit(InMenu::BuildingMenu),
+            |mut transfer: ResMut<Transfer>| transfer.result.clear(),
+        )
+        .add_systems(
+            OnExit(GameState::InGame),
+            |mut inspected: ResMut<BuildingInspected>, mut transfer: ResMut<Transfer>| {
+                inspected.0 = None;
+                *transfer = Transfer::default();
+            },
+        )
+        .insert_resource(BuildingInspected(None))
+        .init_resource::<Transfer>();
     // .add_event::<InspectBuilding>();
 }
 
@@ -27,6 +92,181 @@ struct BuildingMenu;
 #[derive(Component)]
 struct BuildingMenuSlot;
 
+#[derive(Component)]
+struct ProductionStatus;
+
+#[derive(Component)]
+struct TurretStatus;
+
+/// How many units a transfer button moves, the last one moving everything.
+const TRANSFER_AMOUNTS: [usize; 4] = [1, 5, 25, usize::MAX];
+
+/// Where the inspected hold moves goods to and from, and how many at a time.
+#[derive(Resource, Default)]
+struct Transfer {
+    target: Option<TransferTarget>,
+    /// Index into [`TRANSFER_AMOUNTS`].
+    amount: usize,
+    /// What the last transfer did.
+    result: String,
+}
+
+/// Something the inspected hold can move goods to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TransferTarget {
+    Hold(Entity),
+    /// An index into [`ActiveContracts`]. Contracts only take the goods they require and don't
+    /// give them back.
+    Contract(usize),
+}
+
+/// What the transfer panel shows of the target.
+enum TargetGoods<'a> {
+    Hold(&'a Inventory),
+    Contract(&'a Contract),
+}
+
+impl TargetGoods<'_> {
+    /// Whether the panel offers to move `item` between `source` and the target.
+    fn deals_in(&self, item: &Item, source: &Inventory) -> bool {
+        match self {
+            TargetGoods::Hold(inventory) => source.count(item) > 0 || inventory.count(item) > 0,
+            TargetGoods::Contract(contract) => contract.required.0 == *item,
+        }
+    }
+
+    fn describe(&self, item: &Item) -> String {
+        match self {
+            TargetGoods::Hold(inventory) => inventory.count(item).to_string(),
+            TargetGoods::Contract(contract) => format!(
+                "{}/{} handed over",
+                contract.handed_over, contract.required.1
+            ),
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum BuildingAction {
+    Demolish,
+    Move,
+    Upgrade,
+}
+
+#[derive(Component)]
+struct CrewButton {
+    assign: bool,
+}
+
+#[derive(Component)]
+struct TransferTargetButton;
+
+#[derive(Component)]
+struct TransferAmountButton(usize);
+
+#[derive(Component)]
+struct TransferButton {
+    item: Item,
+    /// Whether goods go from the inspected hold to the target rather than the other way.
+    send: bool,
+}
+
+/// Every inventory on the train, for picking a transfer target.
+type HoldNames<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static GlobalTransform,
+        Option<&'static Building>,
+        Option<&'static TrainCar>,
+        Has<Locomotive>,
+    ),
+    With<Inventory>,
+>;
+
+/// Names the train's inventories in the order production fills them, then the contracts goods
+/// can be handed over to, numbering any that would otherwise share a name.
+fn transfer_targets(
+    holds: &HoldNames,
+    definitions: &Buildings,
+    contracts: &ActiveContracts,
+    items: &ItemRegistry,
+) -> Vec<(TransferTarget, String)> {
+    let mut named = holds
+        .iter()
+        .map(|(entity, transform, building, car, is_locomotive)| {
+            let name = if is_locomotive {
+                "Locomotive"
+            } else if let Some(Building(building_type)) = building {
+                definitions
+                    .get(building_type)
+                    .map_or("Building", |it| it.name.as_str())
+            } else if let Some(TrainCar(car_type)) = car {
+                car_type.name()
+            } else {
+                "Cargo"
+            };
+            ((is_locomotive, transform.translation().x), entity, name)
+        })
+        .collect::<Vec<_>>();
+    named.sort_by(|(a, ..), (b, ..)| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    let named = named
+        .into_iter()
+        .map(|(_, entity, name)| (TransferTarget::Hold(entity), name.to_string()))
+        .chain(contracts.0.iter().enumerate().map(|(i, contract)| {
+            (
+                TransferTarget::Contract(i),
+                format!("{} contract", items.name(&contract.required.0)),
+            )
+        }))
+        .collect::<Vec<_>>();
+
+    let mut seen = HashMap::<&str, usize>::new();
+    named
+        .iter()
+        .map(|(target, name)| {
+            let shared = named.iter().filter(|(_, it)| it == name).count() > 1;
+            let number = seen.entry(name).or_insert(0);
+            *number += 1;
+            if shared {
+                (*target, format!("{name} {number}"))
+            } else {
+                (*target, name.clone())
+            }
+        })
+        .collect()
+}
+
+/// The chosen target, or the first inventory on the train that isn't `source`.
+fn transfer_target(
+    transfer: &Transfer,
+    source: Entity,
+    targets: &[(TransferTarget, String)],
+) -> Option<(TransferTarget, String)> {
+    let other = |target: &TransferTarget| *target != TransferTarget::Hold(source);
+    targets
+        .iter()
+        .find(|(target, _)| Some(*target) == transfer.target && other(target))
+        .or_else(|| targets.iter().find(|(target, _)| other(target)))
+        .cloned()
+}
+
+/// Opens the building menu on the clicked building, or on the locomotive or a car to get at
+/// their holds.
+pub fn inspect_on_click(
+    mut trigger: Trigger<Pointer<Click>>,
+    mut inspected: ResMut<BuildingInspected>,
+    mut menu_state: ResMut<NextState<InMenu>>,
+    current_menu_state: Res<State<InMenu>>,
+) {
+    if let InMenu::None = **current_menu_state {
+        inspected.0 = Some(trigger.target());
+        menu_state.set(InMenu::BuildingMenu);
+        trigger.propagate(false);
+    }
+}
+
 // #[derive(Event)]
 // pub struct InspectBuilding {
 //     pub building: Entity,
@@ -35,6 +275,7 @@ struct BuildingMenuSlot;
 fn spawn_building_menu(mut commands: Commands) {
     commands
         .spawn((
+            StateScoped(GameState::InGame),
             Node {
                 width: Val::Vw(60.0),
                 height: Val::Vh(60.0),
@@ -89,49 +330,831 @@ fn show_building_menu(mut menu: Query<&mut Visibility, With<BuildingMenu>>) {
 }
 
 fn hide_building_menu(mut menu: Query<&mut Visibility, With<BuildingMenu>>) {
-    *menu.single_mut().unwrap() = Visibility::Hidden;
+    // the menu is already gone when leaving the game with it open
+    if let Ok(mut menu) = menu.single_mut() {
+        *menu = Visibility::Hidden;
+    }
 }
 
 fn update_inspected_building(
     mut inspected_building: ResMut<BuildingInspected>,
-    buildings: Query<(&Building, Option<&Inventory>)>,
+    buildings: Query<(
+        &Building,
+        &BuildingTier,
+        &ChildOf,
+        Option<&Inventory>,
+        Option<&ResourceProduction>,
+        Option<&Capacity>,
+    )>,
+    cars: Query<&TrainCar>,
+    hold_contents: Query<(&Inventory, Option<&Capacity>)>,
+    holds: HoldNames,
+    contracts: Res<ActiveContracts>,
+    transfer: Res<Transfer>,
+    crew: Query<(&CrewMember, Option<&WorksAt>)>,
+    occupants: Query<&Occupants>,
+    workers: Query<&Workers>,
+    children: Query<&Children>,
     building_menu_slot: Single<Entity, With<BuildingMenuSlot>>,
+    definitions: Buildings,
+    items: Items,
     mut commands: Commands,
     font_assets: Res<FontAssets>,
 ) {
     let Some(entity) = inspected_building.0 else {
         return;
     };
-    let Ok((building, inventory)) = buildings.get(entity) else {
-        inspected_building.0 = None;
+    let targets = transfer_targets(&holds, &definitions, &contracts, &items);
+    let Ok((building, BuildingTier(level), child_of, inventory, production, capacity)) =
+        buildings.get(entity)
+    else {
+        // the locomotive and cars only have their own hold to show
+        let Ok((inventory, capacity)) = hold_contents.get(entity) else {
+            inspected_building.0 = None;
+            return;
+        };
+        let name = targets
+            .iter()
+            .find(|(it, _)| *it == TransferTarget::Hold(entity))
+            .map_or("Cargo".to_string(), |(_, name)| name.clone());
+        commands
+            .entity(*building_menu_slot)
+            .despawn_related::<Children>()
+            .with_children(|parent| {
+                parent.spawn((Text::new(name), TextColor::BLACK));
+                spawn_hold(
+                    parent,
+                    &font_assets.default_font,
+                    &items,
+                    (entity, inventory, capacity),
+                    &targets,
+                    &hold_contents,
+                    &contracts,
+                    &transfer,
+                );
+            });
         return;
     };
+    let Some(definition) = definitions.get(&building.0) else {
+        return;
+    };
+    let next_tier = definition.tiers.get(level + 1);
 
     commands
         .entity(*building_menu_slot)
         .despawn_related::<Children>()
         .with_children(|parent| {
-            parent.spawn((Text::new(building.0.name()), TextColor::BLACK));
-            match building.0 {
-                super::BuildingType::Housing => {}
-                super::BuildingType::Farm => {}
-                super::BuildingType::Storage => {
-                    for (item, amount) in &inventory.unwrap().items {
-                        // parent.spawn((Text::new(item.name())));
+            parent.spawn((Text::new(definition.tier_name(*level)), TextColor::BLACK));
+            let stacked = stacked_buildings(entity, &children, &buildings);
+            parent
+                .spawn(Node {
+                    display: Display::Flex,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(5.0),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    let upgrade = next_tier.map(|tier| {
+                        (
+                            BuildingAction::Upgrade,
+                            format!("Upgrade to {} for", definition.tier_name(level + 1)),
+                            tier.upgrade_cost.clone(),
+                            can_afford(hold_contents.iter().map(|(it, _)| it), &tier.upgrade_cost),
+                        )
+                    });
+                    for (button, label, cost, available) in [
+                        (
+                            BuildingAction::Demolish,
+                            "Demolish for".to_string(),
+                            definition.refund(*level),
+                            true,
+                        ),
+                        (BuildingAction::Move, "Move".to_string(), Vec::new(), true),
+                    ]
+                    .into_iter()
+                    .chain(upgrade)
+                    {
+                        let text = (
+                            TextColor(if available {
+                                Color::BLACK
+                            } else {
+                                UNAVAILABLE_COLOR
+                            }),
+                            TextFont::from_font(font_assets.default_font.clone()),
+                        );
+                        parent
+                            .spawn((
+                                Button,
+                                button,
+                                Node {
+                                    display: Display::Flex,
+                                    align_items: AlignItems::Center,
+                                    column_gap: Val::Px(5.0),
+                                    padding: UiRect::horizontal(Val::Px(5.0)),
+                                    ..Default::default()
+                                },
+                                BackgroundColor(TRANSFER_BUTTON_COLOR),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((Text::new(label), text.clone()));
+                                spawn_items(parent, &items, &cost, text);
+                            });
+                    }
+                    if stacked.len() > 1 {
                         parent.spawn((
                             TextColor::BLACK,
-                            Text::new(format!("{}x{}", item.name(), amount)),
+                            Text::new(format!("{} built on top come along", stacked.len() - 1)),
                             TextFont::from_font(font_assets.default_font.clone()),
                         ));
                     }
-                    if inventory.unwrap().is_empty() {
+                });
+            if let Some(production) = production {
+                let text = (
+                    TextColor::BLACK,
+                    TextFont::from_font(font_assets.default_font.clone()),
+                );
+                parent
+                    .spawn(Node {
+                        display: Display::Flex,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(5.0),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        if production.recipe.inputs.is_empty() {
+                            parent.spawn((Text::new("Nothing"), text.clone()));
+                        } else {
+                            spawn_items(parent, &items, &production.recipe.inputs, text.clone());
+                        }
+                        parent.spawn((Text::new("->"), text.clone()));
+                        spawn_items(parent, &items, &production.recipe.outputs, text.clone());
+                        parent.spawn((
+                            Text::new(format!(
+                                "every {}s",
+                                production.timer.duration().as_secs_f32()
+                            )),
+                            text,
+                        ));
+                    });
+                parent.spawn((
+                    TextColor::BLACK,
+                    Text::default(),
+                    TextFont::from_font(font_assets.default_font.clone()),
+                    ProductionStatus,
+                ));
+            }
+            let needed = definition.crew;
+            if needed > 0 {
+                let names = workers
+                    .get(entity)
+                    .into_iter()
+                    .flat_map(|it| it.iter())
+                    .filter_map(|it| crew.get(it).ok())
+                    .map(|(member, _)| member.name.as_str())
+                    .collect::<Vec<_>>();
+                parent
+                    .spawn(Node {
+                        display: Display::Flex,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(5.0),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
                         parent.spawn((
                             TextColor::BLACK,
-                            Text::new("Empty"),
+                            Text::new(format!(
+                                "Crew: {}/{needed} {}({} idle on the train)",
+                                names.len(),
+                                names.iter().map(|it| format!("{it} ")).collect::<String>(),
+                                crew.iter().filter(|(_, it)| it.is_none()).count()
+                            )),
                             TextFont::from_font(font_assets.default_font.clone()),
                         ));
-                    }
+                        for assign in [true, false] {
+                            parent.spawn((
+                                Button,
+                                CrewButton { assign },
+                                Node {
+                                    padding: UiRect::horizontal(Val::Px(5.0)),
+                                    ..Default::default()
+                                },
+                                BackgroundColor(TRANSFER_BUTTON_COLOR),
+                                children![(
+                                    TextColor::BLACK,
+                                    Text::new(if assign { "Assign" } else { "Unassign" }),
+                                    TextFont::from_font(font_assets.default_font.clone()),
+                                )],
+                            ));
+                        }
+                    });
+            }
+            if definition.is_housing() {
+                let residents = occupants
+                    .get(entity)
+                    .into_iter()
+                    .flat_map(|it| it.iter())
+                    .filter_map(|it| crew.get(it).ok())
+                    .collect::<Vec<_>>();
+                parent.spawn((
+                    TextColor::BLACK,
+                    Text::new(format!(
+                        "Beds: {}/{}",
+                        residents.len(),
+                        beds(definition.tier(*level), cars.get(child_of.parent()).ok())
+                    )),
+                    TextFont::from_font(font_assets.default_font.clone()),
+                ));
+                for (member, works_at) in residents {
+                    let job = works_at
+                        .and_then(|WorksAt(workplace)| buildings.get(*workplace).ok())
+                        .and_then(|(building, ..)| definitions.get(&building.0))
+                        .map_or("idle", |it| it.name.as_str());
+                    parent.spawn((
+                        TextColor(if member.morale < LOW_MORALE {
+                            LOW_MORALE_COLOR
+                        } else {
+                            Color::BLACK
+                        }),
+                        Text::new(format!(
+                            "{}, {job}, morale {:.0}%",
+                            member.name,
+                            member.morale * 100.0
+                        )),
+                        TextFont::from_font(font_assets.default_font.clone()),
+                    ));
                 }
             }
+            if definition.turret.is_some() {
+                parent.spawn((
+                    TextColor::BLACK,
+                    Text::default(),
+                    TextFont::from_font(font_assets.default_font.clone()),
+                    TurretStatus,
+                ));
+            }
+            if definition.is_storage() {
+                spawn_hold(
+                    parent,
+                    &font_assets.default_font,
+                    &items,
+                    (entity, inventory.unwrap(), capacity),
+                    &targets,
+                    &hold_contents,
+                    &contracts,
+                    &transfer,
+                );
+            }
+        });
+}
+
+/// How full a hold is, with a panel for moving its goods to and from another hold or handing
+/// them over to a contract.
+fn spawn_hold(
+    parent: &mut ChildSpawnerCommands,
+    font: &Handle<Font>,
+    items: &ItemRegistry,
+    (source, inventory, capacity): (Entity, &Inventory, Option<&Capacity>),
+    targets: &[(TransferTarget, String)],
+    hold_contents: &Query<(&Inventory, Option<&Capacity>)>,
+    contracts: &ActiveContracts,
+    transfer: &Transfer,
+) {
+    parent.spawn((
+        TextColor::BLACK,
+        Text::new(format!(
+            "Stored: {}/{}",
+            inventory.total(),
+            capacity.map_or(0, |it| it.0)
+        )),
+        TextFont::from_font(font.clone()),
+    ));
+    if inventory.is_empty() {
+        parent.spawn((
+            TextColor::BLACK,
+            Text::new("Empty"),
+            TextFont::from_font(font.clone()),
+        ));
+    }
+    let Some((target, target_name)) = transfer_target(transfer, source, targets) else {
+        return;
+    };
+    let target_goods = match target {
+        TransferTarget::Hold(entity) => match hold_contents.get(entity) {
+            Ok((inventory, _)) => TargetGoods::Hold(inventory),
+            Err(_) => return,
+        },
+        TransferTarget::Contract(i) => TargetGoods::Contract(&contracts.0[i]),
+    };
+    spawn_transfer_panel(
+        parent,
+        font,
+        items,
+        inventory,
+        &target_name,
+        &target_goods,
+        transfer,
+    );
+}
+
+/// Lists the hold's goods next to the target's, with buttons to move them either way. Goods
+/// handed over to a contract only go one way.
+fn spawn_transfer_panel(
+    parent: &mut ChildSpawnerCommands,
+    font: &Handle<Font>,
+    items: &ItemRegistry,
+    inventory: &Inventory,
+    target_name: &str,
+    target: &TargetGoods,
+    transfer: &Transfer,
+) {
+    parent.spawn((
+        Node {
+            display: Display::Flex,
+            column_gap: Val::Px(5.0),
+            ..Default::default()
+        },
+        children![
+            (
+                TextColor::BLACK,
+                Text::new("Move to or from:"),
+                TextFont::from_font(font.clone()),
+            ),
+            (
+                Button,
+                TransferTargetButton,
+                Node {
+                    padding: UiRect::horizontal(Val::Px(5.0)),
+                    ..Default::default()
+                },
+                BackgroundColor(TRANSFER_BUTTON_COLOR),
+                children![(
+                    TextColor::BLACK,
+                    Text::new(format!("{target_name} (next)")),
+                    TextFont::from_font(font.clone()),
+                )],
+            )
+        ],
+    ));
+    parent
+        .spawn(Node {
+            display: Display::Flex,
+            column_gap: Val::Px(5.0),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextColor::BLACK,
+                Text::new("Amount:"),
+                TextFont::from_font(font.clone()),
+            ));
+            for (i, amount) in TRANSFER_AMOUNTS.iter().enumerate() {
+                parent.spawn((
+                    Button,
+                    TransferAmountButton(i),
+                    Node {
+                        padding: UiRect::horizontal(Val::Px(5.0)),
+                        ..Default::default()
+                    },
+                    BackgroundColor(if i == transfer.amount {
+                        SELECTED_AMOUNT_COLOR
+                    } else {
+                        TRANSFER_BUTTON_COLOR
+                    }),
+                    children![(
+                        TextColor::BLACK,
+                        Text::new(if *amount == usize::MAX {
+                            "All".to_string()
+                        } else {
+                            amount.to_string()
+                        }),
+                        TextFont::from_font(font.clone()),
+                    )],
+                ));
+            }
         });
+
+    for item in items
+        .iter()
+        .map(|it| &it.id)
+        .filter(|it| target.deals_in(it, inventory))
+    {
+        parent
+            .spawn(Node {
+                display: Display::Flex,
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(5.0),
+                ..Default::default()
+            })
+            .with_children(|parent| {
+                parent
+                    .spawn(Node {
+                        display: Display::Flex,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(5.0),
+                        width: Val::Px(260.0),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        let text = (TextColor::BLACK, TextFont::from_font(font.clone()));
+                        spawn_items(
+                            parent,
+                            items,
+                            &[(item.clone(), inventory.count(item))],
+                            text.clone(),
+                        );
+                        parent.spawn((
+                            Text::new(format!("({target_name}: {})", target.describe(item))),
+                            text,
+                        ));
+                    });
+                let to_contract = matches!(target, TargetGoods::Contract(_));
+                for send in [true, false].into_iter().filter(|it| *it || !to_contract) {
+                    parent.spawn((
+                        Button,
+                        TransferButton {
+                            item: item.clone(),
+                            send,
+                        },
+                        Node {
+                            padding: UiRect::horizontal(Val::Px(5.0)),
+                            ..Default::default()
+                        },
+                        BackgroundColor(TRANSFER_BUTTON_COLOR),
+                        children![(
+                            TextColor::BLACK,
+                            Text::new(match (send, to_contract) {
+                                (true, true) => "Hand over",
+                                (true, false) => "Send",
+                                (false, _) => "Take",
+                            }),
+                            TextFont::from_font(font.clone()),
+                        )],
+                    ));
+                }
+            });
+    }
+    parent.spawn((
+        TextColor::BLACK,
+        Text::new(transfer.result.clone()),
+        TextFont::from_font(font.clone()),
+    ));
+}
+
+/// The building and every building stacked on it, bottom first.
+fn stacked_buildings<D: QueryData, F: QueryFilter>(
+    entity: Entity,
+    children: &Query<&Children>,
+    buildings: &Query<D, F>,
+) -> Vec<Entity> {
+    std::iter::once(entity)
+        .chain(children.iter_descendants(entity))
+        .filter(|it| buildings.contains(*it))
+        .collect()
+}
+
+/// Demolishing refunds part of the cost of the whole stack and moves whatever the stack held
+/// to the rest of the train, anything that doesn't fit is lost. Whoever lived in the stack
+/// moves to other housing, see [`Rehouse`]. Moving hands over to the build
+/// menu, see [`Relocating`]. Upgrading applies the next of the building's [`Tier`](super::tiers::Tier)s.
+fn building_actions(
+    interaction_query: Query<(&Interaction, &BuildingAction), Changed<Interaction>>,
+    mut inspected_building: ResMut<BuildingInspected>,
+    buildings: Query<(&Building, &BuildingTier, &Transform, &ChildOf)>,
+    mut upgraded: Query<(&mut Sprite, &mut Health, Option<&mut ResourceProduction>)>,
+    children: Query<&Children>,
+    mut holds: Holds,
+    mut building_type: ResMut<BuildingType>,
+    mut relocating: ResMut<Relocating>,
+    mut next_state: ResMut<NextState<InMenu>>,
+    current_stop: Res<CurrentStop>,
+    definitions: Buildings,
+    items: Items,
+    mut commands: Commands,
+) {
+    let Some(entity) = inspected_building.0 else {
+        return;
+    };
+    let Ok((building, BuildingTier(level), transform, child_of)) = buildings.get(entity) else {
+        return;
+    };
+    let Some(definition) = definitions.get(&building.0) else {
+        return;
+    };
+    for (interaction, action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match action {
+            BuildingAction::Demolish => {
+                let stack = stacked_buildings(entity, &children, &buildings);
+                let mut salvage = Vec::new();
+                for stacked in &stack {
+                    let (Building(stacked_type), BuildingTier(stacked_level), ..) =
+                        buildings.get(*stacked).unwrap();
+                    if let Some(stacked_definition) = definitions.get(stacked_type) {
+                        salvage.extend(stacked_definition.refund(*stacked_level));
+                    }
+                    if let Ok((_, inventory, ..)) = holds.get(*stacked) {
+                        salvage.extend(
+                            inventory
+                                .items
+                                .iter()
+                                .map(|(item, amount)| (item.clone(), *amount)),
+                        );
+                    }
+                }
+                let lost = store_what_fits(&mut holds, &salvage, &stack);
+                info!(
+                    "Demolished {} and {} on top",
+                    definition.name,
+                    stack.len() - 1
+                );
+                if !lost.is_empty() {
+                    warn!(
+                        "No room on the train for {}, it was left behind",
+                        format_cost(&lost, &items)
+                    );
+                }
+                commands.queue(Rehouse(stack));
+                commands.entity(entity).despawn();
+                commands.queue(RestoreBuildLocation {
+                    parent: child_of.parent(),
+                    offset: transform.translation.xy(),
+                });
+                inspected_building.0 = None;
+                next_state.set(InMenu::None);
+            }
+            BuildingAction::Move => {
+                *building_type = building.0.clone();
+                relocating.0 = Some(entity);
+                inspected_building.0 = None;
+                next_state.set(InMenu::BuildMenu);
+            }
+            BuildingAction::Upgrade => {
+                let Some(tier) = definition.tiers.get(level + 1) else {
+                    continue;
+                };
+                if !can_afford(holds.iter().map(|(_, it, ..)| it), &tier.upgrade_cost) {
+                    continue;
+                }
+                for (item, amount) in &tier.upgrade_cost {
+                    take_items(holds.iter_mut().map(|(_, it, ..)| it), item, *amount);
+                }
+                let previous = definition.tier(*level);
+                let (mut sprite, mut health, production) = upgraded.get_mut(entity).unwrap();
+                sprite.image = definition.tier_texture(level + 1);
+                sprite.color = tier.tint();
+                let factor = tier.health / previous.health;
+                health.max *= factor;
+                health.current *= factor;
+                if let Some(mut production) = production {
+                    production.set_tier(tier);
+                }
+                commands.entity(entity).insert(BuildingTier(level + 1));
+                if definition.is_storage() {
+                    commands.entity(entity).insert(Capacity(tier.capacity));
+                }
+                if definition.is_housing() && can_hire(&current_stop) {
+                    commands.queue(MoveIn(entity));
+                }
+                info!("Upgraded to {}", definition.tier_name(level + 1));
+                inspected_building.set_changed();
+            }
+        }
+    }
+}
+
+/// Assigns the most cheerful idle crew member, or sends the last one assigned back to idle.
+fn crew_buttons(
+    interaction_query: Query<(&Interaction, &CrewButton), Changed<Interaction>>,
+    mut inspected_building: ResMut<BuildingInspected>,
+    buildings: Query<(&Building, Option<&Workers>)>,
+    idle: Query<(Entity, &CrewMember), Without<WorksAt>>,
+    definitions: Buildings,
+    mut commands: Commands,
+) {
+    let Some((entity, (building, workers))) = inspected_building
+        .0
+        .and_then(|entity| Some((entity, buildings.get(entity).ok()?)))
+    else {
+        return;
+    };
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if button.assign {
+            if staffed(workers) >= definitions.get(&building.0).map_or(0, |it| it.crew) {
+                continue;
+            }
+            let Some((member, _)) = idle
+                .iter()
+                .max_by(|(_, a), (_, b)| a.morale.total_cmp(&b.morale))
+            else {
+                info!("Nobody on the train is free to work");
+                continue;
+            };
+            commands.entity(member).insert(WorksAt(entity));
+        } else if let Some(worker) = workers.and_then(|it| it.iter().last()) {
+            commands.entity(worker).remove::<WorksAt>();
+        }
+        inspected_building.set_changed();
+    }
+}
+
+fn transfer_target_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<TransferTargetButton>)>,
+    inspected_building: Res<BuildingInspected>,
+    holds: HoldNames,
+    contracts: Res<ActiveContracts>,
+    definitions: Buildings,
+    items: Items,
+    mut transfer: ResMut<Transfer>,
+) {
+    let Some(source) = inspected_building.0 else {
+        return;
+    };
+    for interaction in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let targets = transfer_targets(&holds, &definitions, &contracts, &items);
+        let Some((current, _)) = transfer_target(&transfer, source, &targets) else {
+            continue;
+        };
+        let position = targets.iter().position(|(it, _)| *it == current).unwrap();
+        transfer.target = targets
+            .iter()
+            .cycle()
+            .skip(position + 1)
+            .take(targets.len())
+            .find(|(it, _)| *it != TransferTarget::Hold(source))
+            .map(|(it, _)| *it);
+        transfer.result.clear();
+    }
+}
+
+fn transfer_amount_buttons(
+    interaction_query: Query<(&Interaction, &TransferAmountButton), Changed<Interaction>>,
+    mut transfer: ResMut<Transfer>,
+) {
+    for (interaction, TransferAmountButton(i)) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            transfer.amount = *i;
+        }
+    }
+}
+
+/// Moves as much as was asked for, the owner has and the receiving end has room for. A
+/// contract takes no more than it still requires.
+fn transfer_buttons(
+    interaction_query: Query<(&Interaction, &TransferButton), Changed<Interaction>>,
+    inspected_building: Res<BuildingInspected>,
+    holds: HoldNames,
+    definitions: Buildings,
+    mut inventories: Query<(&mut Inventory, Option<&AcceptedItems>, Option<&Capacity>)>,
+    mut contracts: ResMut<ActiveContracts>,
+    mut transfer: ResMut<Transfer>,
+    items: Items,
+) {
+    let Some(source) = inspected_building.0 else {
+        return;
+    };
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some((target, target_name)) = transfer_target(
+            &transfer,
+            source,
+            &transfer_targets(&holds, &definitions, &contracts, &items),
+        ) else {
+            continue;
+        };
+        let item = &button.item;
+        let name = items.name(item);
+        let amount = TRANSFER_AMOUNTS[transfer.amount];
+        let (wanted, moved) = match target {
+            TransferTarget::Hold(target) => {
+                let (from, to) = if button.send {
+                    (source, target)
+                } else {
+                    (target, source)
+                };
+                let Ok([(mut from, ..), (mut to, accepted, capacity)]) =
+                    inventories.get_many_mut([from, to])
+                else {
+                    continue;
+                };
+                let wanted = amount.min(from.count(item));
+                let moved = wanted.min(to.space_for(item, accepted, capacity));
+                if moved > 0 {
+                    *from.items.get_mut(item).unwrap() -= moved;
+                    *to.items.entry(item.clone()).or_insert(0) += moved;
+                }
+                (wanted, moved)
+            }
+            TransferTarget::Contract(i) => {
+                let (Ok((mut from, ..)), Some(contract)) =
+                    (inventories.get_mut(source), contracts.0.get_mut(i))
+                else {
+                    continue;
+                };
+                if !button.send || contract.required.0 != *item {
+                    continue;
+                }
+                let wanted = amount.min(from.count(item));
+                let moved = wanted.min(contract.outstanding());
+                if moved > 0 {
+                    *from.items.get_mut(item).unwrap() -= moved;
+                    contract.handed_over += moved;
+                }
+                (wanted, moved)
+            }
+        };
+
+        let to_contract = matches!(target, TransferTarget::Contract(_));
+        let direction = if button.send { "to" } else { "from" };
+        transfer.result = if wanted == 0 {
+            format!("No {name} to move")
+        } else if moved == 0 && to_contract {
+            format!("The {target_name} needs no more {name}")
+        } else if moved == 0 {
+            format!("No room for {name}")
+        } else if moved < wanted && to_contract {
+            format!("Handed over {moved} {name} to the {target_name}, the rest isn't needed")
+        } else if moved < wanted {
+            format!("Moved {moved} {name} {direction} {target_name}, the rest didn't fit")
+        } else if to_contract {
+            format!("Handed over {moved} {name} to the {target_name}")
+        } else {
+            format!("Moved {moved} {name} {direction} {target_name}")
+        };
+        info!("{}", transfer.result);
+    }
+}
+
+fn update_production_status(
+    inspected_building: Res<BuildingInspected>,
+    productions: Query<&ResourceProduction>,
+    items: Items,
+    mut status: Query<(&mut Text, &mut TextColor), With<ProductionStatus>>,
+) {
+    let Some(production) = inspected_building
+        .0
+        .and_then(|entity| productions.get(entity).ok())
+    else {
+        return;
+    };
+    for (mut text, mut color) in &mut status {
+        (**text, color.0) = match &production.stalled_on {
+            _ if production.understaffed => (
+                "Paused: assign crew to work here".to_string(),
+                Color::srgb(0.8, 0., 0.),
+            ),
+            _ if production.output_blocked => (
+                "Storage full, production paused".to_string(),
+                Color::srgb(0.8, 0., 0.),
+            ),
+            Some(item) => (
+                format!("Stalled: not enough {} on the train", items.name(item)),
+                Color::srgb(0.8, 0., 0.),
+            ),
+            None if production.running => (
+                format!("Progress: {:.0}%", production.timer.fraction() * 100.0),
+                Color::BLACK,
+            ),
+            None => ("Idle".to_string(), Color::BLACK),
+        };
+    }
+}
+
+fn update_turret_status(
+    inspected_building: Res<BuildingInspected>,
+    turrets: Query<&Turret>,
+    inventories: Query<&Inventory>,
+    items: Items,
+    mut status: Query<(&mut Text, &mut TextColor), With<TurretStatus>>,
+) {
+    let Some(turret) = inspected_building
+        .0
+        .and_then(|entity| turrets.get(entity).ok())
+    else {
+        return;
+    };
+    let ammo = total_owned(inventories.iter(), &turret.stats.ammo);
+    for (mut text, mut color) in &mut status {
+        **text = format!(
+            "Ammo: {} {} ({} per shot)\nKills: {}",
+            ammo,
+            items.name(&turret.stats.ammo),
+            turret.stats.ammo_per_shot,
+            turret.kills
+        );
+        color.0 = if turret.out_of_ammo {
+            Color::srgb(0.8, 0., 0.)
+        } else {
+            Color::BLACK
+        };
+    }
 }
@@ -0,0 +1,316 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    ecs::system::SystemParam,
+    prelude::*,
+};
+use bevy_asset_loader::asset_collection::AssetCollection;
+use serde::Deserialize;
+
+use crate::resources_plugin::Item;
+
+use super::{
+    BuildingType, DEMOLITION_REFUND, Slot, recipes::Recipe, tiers::Tier, turrets::TurretStats,
+};
+
+/// Every building there is, one `<id>.building.ron` file each in `assets/buildings`.
+///
+/// The web build cannot load a whole folder, so `assets/buildings.ron` lists the files and a
+/// new building only needs a line there. With the `hot_reload` feature edits to either file
+/// apply while the game runs.
+#[derive(AssetCollection, Resource)]
+pub struct BuildingAssets {
+    #[asset(path = "buildings.ron")]
+    pub index: Handle<BuildingIndex>,
+}
+
+impl BuildingAssets {
+    pub fn get<'a>(
+        &self,
+        indices: &'a Assets<BuildingIndex>,
+        definitions: &'a Assets<BuildingDefinition>,
+        building_type: &BuildingType,
+    ) -> Option<&'a BuildingDefinition> {
+        self.iter(indices, definitions)
+            .find(|it| it.id == *building_type)
+    }
+
+    fn iter<'a>(
+        &self,
+        indices: &'a Assets<BuildingIndex>,
+        definitions: &'a Assets<BuildingDefinition>,
+    ) -> impl Iterator<Item = &'a BuildingDefinition> {
+        indices
+            .get(&self.index)
+            .into_iter()
+            .flat_map(|it| &it.0)
+            .filter_map(|it| definitions.get(it))
+    }
+}
+
+/// The building files listed in `assets/buildings.ron`.
+#[derive(Asset, TypePath)]
+pub struct BuildingIndex(Vec<Handle<BuildingDefinition>>);
+
+/// Looks up the definitions of buildings, as they are after any hot reload.
+#[derive(SystemParam)]
+pub struct Buildings<'w> {
+    assets: Res<'w, BuildingAssets>,
+    indices: Res<'w, Assets<BuildingIndex>>,
+    definitions: Res<'w, Assets<BuildingDefinition>>,
+}
+
+impl Buildings<'_> {
+    pub fn get(&self, building_type: &BuildingType) -> Option<&BuildingDefinition> {
+        self.assets
+            .get(&self.indices, &self.definitions, building_type)
+    }
+
+    /// Every building in the order the build menu lists them.
+    pub fn iter(&self) -> impl Iterator<Item = &BuildingDefinition> {
+        let mut definitions = self
+            .assets
+            .iter(&self.indices, &self.definitions)
+            .collect::<Vec<_>>();
+        definitions.sort_by_key(|it| (it.order, it.name.clone()));
+        definitions.into_iter()
+    }
+}
+
+/// The definition of `building_type` for commands working on the [`World`] directly.
+pub fn definition<'w>(
+    world: &'w World,
+    building_type: &BuildingType,
+) -> Option<&'w BuildingDefinition> {
+    world.resource::<BuildingAssets>().get(
+        world.resource::<Assets<BuildingIndex>>(),
+        world.resource::<Assets<BuildingDefinition>>(),
+        building_type,
+    )
+}
+
+#[derive(Asset, TypePath)]
+pub struct BuildingDefinition {
+    /// Taken from the file name, this is what saves refer to the building by.
+    pub id: BuildingType,
+    pub name: String,
+    /// Position in the build menu.
+    pub order: u32,
+    pub texture: Handle<Image>,
+    /// The part of the texture the building itself takes up, shown in the build menu.
+    pub footprint: Rect,
+    /// The kinds of build location the building may be placed on.
+    pub fits: Vec<Slot>,
+    /// Build locations on top of the building for stacking others onto it.
+    pub slots: Vec<(Vec2, Slot)>,
+    pub cost: Vec<(Item, usize)>,
+    /// Weight of the building itself in tonnes, not counting what it stores.
+    pub weight: f32,
+    pub health: f32,
+    /// Crew that must be assigned before the building produces anything.
+    pub crew: usize,
+    pub production: Option<Recipe>,
+    pub turret: Option<TurretStats>,
+    /// At least one, the first being what gets built.
+    pub tiers: Vec<Tier>,
+}
+
+impl BuildingDefinition {
+    /// Whether this building may be placed on a build location of the given kind.
+    pub fn fits(&self, slot: Slot) -> bool {
+        self.fits.contains(&slot)
+    }
+
+    /// Whether crew sleep here.
+    pub fn is_housing(&self) -> bool {
+        self.tiers[0].beds > 0
+    }
+
+    /// Whether the building holds goods of its own.
+    pub fn is_storage(&self) -> bool {
+        self.tiers[0].capacity > 0
+    }
+
+    /// The `level`th tier of this building, falling back to the highest there is.
+    pub fn tier(&self, level: usize) -> &Tier {
+        self.tiers.get(level).unwrap_or(self.tiers.last().unwrap())
+    }
+
+    /// What the building looks like at `level`, the texture of the highest tier up to it that
+    /// has one of its own.
+    pub fn tier_texture(&self, level: usize) -> Handle<Image> {
+        self.tiers
+            .iter()
+            .take(level + 1)
+            .filter_map(Tier::image)
+            .next_back()
+            .unwrap_or(&self.texture)
+            .clone()
+    }
+
+    /// The name with the tier as a roman numeral, e.g. "Farm II".
+    pub fn tier_name(&self, level: usize) -> String {
+        match level {
+            0 => self.name.clone(),
+            1 => format!("{} II", self.name),
+            2 => format!("{} III", self.name),
+            _ => format!("{} {}", self.name, level + 1),
+        }
+    }
+
+    /// What tearing the building down gives back, [`DEMOLITION_REFUND`] of its cost and of
+    /// the upgrades up to `level`.
+    pub fn refund(&self, level: usize) -> Vec<(Item, usize)> {
+        let mut spent: Vec<(Item, usize)> = Vec::new();
+        let upgrades = self
+            .tiers
+            .iter()
+            .take(level + 1)
+            .flat_map(|it| &it.upgrade_cost);
+        for (item, amount) in self.cost.iter().chain(upgrades) {
+            match spent.iter_mut().find(|(it, _)| it == item) {
+                Some((_, total)) => *total += amount,
+                None => spent.push((item.clone(), *amount)),
+            }
+        }
+        spent
+            .into_iter()
+            .filter_map(|(item, amount)| {
+                let refund = (amount as f32 * DEMOLITION_REFUND) as usize;
+                (refund > 0).then_some((item, refund))
+            })
+            .collect()
+    }
+}
+
+/// A `.building.ron` file as written, before its texture is loaded.
+#[derive(Deserialize)]
+struct DefinitionFile {
+    name: String,
+    #[serde(default)]
+    order: u32,
+    texture: String,
+    footprint: Footprint,
+    fits: Vec<Slot>,
+    #[serde(default)]
+    slots: Vec<StackingSlot>,
+    cost: Vec<(Item, usize)>,
+    weight: f32,
+    health: f32,
+    #[serde(default)]
+    crew: usize,
+    #[serde(default)]
+    production: Option<Recipe>,
+    #[serde(default)]
+    turret: Option<TurretStats>,
+    #[serde(default = "default_tiers")]
+    tiers: Vec<Tier>,
+}
+
+#[derive(Deserialize)]
+struct Footprint {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+#[derive(Deserialize)]
+struct StackingSlot {
+    offset: (f32, f32),
+    slot: Slot,
+}
+
+fn default_tiers() -> Vec<Tier> {
+    vec![Tier::default()]
+}
+
+#[derive(Default)]
+pub struct BuildingDefinitionLoader;
+
+impl AssetLoader for BuildingDefinitionLoader {
+    type Asset = BuildingDefinition;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<BuildingDefinition, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut file = ron::de::from_bytes::<DefinitionFile>(&bytes)?;
+        if file.tiers.is_empty() {
+            return Err("a building needs at least one tier".into());
+        }
+        for tier in &mut file.tiers {
+            tier.load_texture(load_context);
+        }
+        let id = load_context
+            .path()
+            .file_name()
+            .and_then(|it| it.to_str())
+            .and_then(|it| it.split('.').next())
+            .ok_or("building files are named after the building")?;
+        let Footprint {
+            x,
+            y,
+            width,
+            height,
+        } = file.footprint;
+        Ok(BuildingDefinition {
+            id: BuildingType(id.to_string()),
+            name: file.name,
+            order: file.order,
+            texture: load_context.load(file.texture),
+            footprint: Rect::new(x, y, x + width, y + height),
+            fits: file.fits,
+            slots: file
+                .slots
+                .into_iter()
+                .map(|it| (Vec2::new(it.offset.0, it.offset.1), it.slot))
+                .collect(),
+            cost: file.cost,
+            weight: file.weight,
+            health: file.health,
+            crew: file.crew,
+            production: file.production,
+            turret: file.turret,
+            tiers: file.tiers,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["building.ron"]
+    }
+}
+
+#[derive(Default)]
+pub struct BuildingIndexLoader;
+
+impl AssetLoader for BuildingIndexLoader {
+    type Asset = BuildingIndex;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<BuildingIndex, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let ids = ron::de::from_bytes::<Vec<String>>(&bytes)?;
+        Ok(BuildingIndex(
+            ids.into_iter()
+                .map(|id| load_context.load(format!("buildings/{id}.building.ron")))
+                .collect(),
+        ))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["buildings.ron"]
+    }
+}
@@ -1,14 +1,28 @@
 use core::f32;
 use std::time::Duration;
 
-use bevy::{math::FloatPow, platform::collections::HashMap, prelude::*, window::PrimaryWindow};
-use building_menus::BuildingInspected;
+use bevy::{math::FloatPow, prelude::*, window::PrimaryWindow};
+pub use building_menus::inspect_on_click;
+use definitions::{
+    BuildingDefinition, BuildingDefinitionLoader, BuildingIndex, BuildingIndexLoader, Buildings,
+    definition,
+};
+use recipes::Recipe;
+use serde::{Deserialize, Serialize};
+use tiers::{BuildingTier, Tier};
+use turrets::Turret;
 
 use crate::{
-    GameState, ImageAssets, InGameState,
-    resources_plugin::{Inventory, Item},
-    train_plugin::TrainState,
+    GameState, InGameState,
+    combat_plugin::Health,
+    crew_plugin::{MoveIn, Workers, can_hire, staffed},
+    resources_plugin::{
+        Capacity, Holds, Inventory, Item, ItemRegistry, Items, can_afford, spawn_items, store_all,
+        take_items, total_owned,
+    },
+    train_plugin::{TrainCar, TrainState},
     ui_state::InMenu,
+    world_plugin::CurrentStop,
 };
 
 // #[derive(States, Debug, Hash, PartialEq, Eq, Clone, Default)]
@@ -18,78 +32,104 @@ use crate::{
 //     NotBuilding,
 // }
 mod building_menus;
+pub mod definitions;
+pub mod recipes;
+pub mod tiers;
+pub mod turrets;
 
-#[derive(Resource, Clone, Copy)]
-pub enum BuildingType {
-    Housing,
-    Farm,
-    Storage,
-}
-
-impl BuildingType {
-    fn get_texture(&self, image_assets: &ImageAssets) -> Handle<Image> {
-        match self {
-            BuildingType::Housing => image_assets.housing.clone(),
-            BuildingType::Farm => image_assets.farm.clone(),
-            _ => image_assets.debug_building.clone(),
-        }
-    }
-    fn get_build_locations(&self) -> Vec<Vec2> {
-        match self {
-            BuildingType::Housing => vec![Vec2::new(0., 40.)],
-            BuildingType::Farm => vec![],
-            BuildingType::Storage => vec![Vec2::new(0., 40.)],
-        }
-    }
-
-    fn iterator() -> impl Iterator<Item = Self> {
-        [Self::Housing, Self::Farm, Self::Storage].into_iter()
-    }
+/// Which building something is, named after its file in `assets/buildings`. What the building
+/// is like is in its [`BuildingDefinition`].
+#[derive(Resource, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct BuildingType(pub String);
 
-    fn name(&self) -> &'static str {
-        match self {
-            BuildingType::Housing => "Housing",
-            BuildingType::Farm => "Farm",
-            BuildingType::Storage => "Storage",
-        }
-    }
+#[derive(Component)]
+pub struct BuildLocation(pub Vec2, pub Slot);
 
-    fn get_resource_production(&self) -> Option<ResourceProduction> {
-        match self {
-            BuildingType::Housing => None,
-            BuildingType::Farm => Some(ResourceProduction(
-                Timer::new(Duration::from_secs_f32(2.0), TimerMode::Repeating),
-                Item::Food,
-            )),
-            BuildingType::Storage => None,
-        }
-    }
+/// What kind of space a [`BuildLocation`] is, which decides the buildings it accepts.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+pub enum Slot {
+    /// The floor of a cargo car.
+    Deck,
+    /// On top of another building.
+    Roof,
+    /// A passenger car seat row, only fit for housing.
+    Cabin,
+    /// A reinforced spot for defences.
+    Mount,
 }
 
-#[derive(Component)]
-pub struct BuildLocation(pub Vec2);
+/// The share of a building's cost that comes back when it is demolished.
+pub const DEMOLITION_REFUND: f32 = 0.5;
+
+/// A building being moved to another spot from the build menu, instead of a new one being built.
+#[derive(Resource, Default)]
+pub struct Relocating(pub Option<Entity>);
 
 #[derive(Component)]
 struct GhostBuilding;
 
 #[derive(Component)]
-pub struct Building(BuildingType);
+pub struct Building(pub BuildingType);
 
 #[derive(Component)]
-pub struct ResourceProduction(pub Timer, pub Item);
+pub struct ResourceProduction {
+    pub timer: Timer,
+    pub recipe: Recipe,
+    /// Whether the inputs for the current cycle have already been taken.
+    pub running: bool,
+    /// The first input the train is short of, if the building is waiting for one.
+    pub stalled_on: Option<Item>,
+    /// Whether a finished cycle is waiting for room to put its outputs.
+    pub output_blocked: bool,
+    /// Whether the building is paused for want of assigned crew.
+    pub understaffed: bool,
+}
+
+impl ResourceProduction {
+    /// Speeds the cycle up or slows it down for `tier`, keeping the progress made so far.
+    pub fn set_tier(&mut self, tier: &Tier) {
+        self.timer.set_duration(Duration::from_secs_f32(
+            self.recipe.seconds * tier.production_time,
+        ));
+    }
+
+    pub fn new(recipe: Recipe) -> Self {
+        Self {
+            timer: Timer::new(Duration::from_secs_f32(recipe.seconds), TimerMode::Once),
+            recipe,
+            running: false,
+            stalled_on: None,
+            output_blocked: false,
+            understaffed: false,
+        }
+    }
+}
 
 pub fn build_plugin(app: &mut App) {
     app //.init_state::<BuildState>()
-        .insert_resource(BuildingType::Farm)
+        .init_asset::<BuildingDefinition>()
+        .init_asset_loader::<BuildingDefinitionLoader>()
+        .init_asset::<BuildingIndex>()
+        .init_asset_loader::<BuildingIndexLoader>()
+        .insert_resource(BuildingType("farm".to_string()))
+        .init_resource::<Relocating>()
         .add_event::<BuildEvent>()
-        .add_plugins(building_menus::building_menus_plugin)
+        .add_plugins((
+            building_menus::building_menus_plugin,
+            turrets::turrets_plugin,
+        ))
         .add_systems(
             Update,
-            (construct_buildings, change_selected_building).run_if(
-                in_state(GameState::InGame)
-                    .and(in_state(InGameState::Running))
-                    .and(in_state(InMenu::BuildMenu)),
-            ),
+            (
+                construct_buildings,
+                change_selected_building,
+                update_blueprint_affordability,
+            )
+                .run_if(
+                    in_state(GameState::InGame)
+                        .and(in_state(InGameState::Running))
+                        .and(in_state(InMenu::BuildMenu)),
+                ),
         )
         .add_systems(
             FixedUpdate,
@@ -110,16 +150,36 @@ pub fn build_plugin(app: &mut App) {
         )
         .add_systems(
             OnExit(InMenu::BuildMenu),
-            |mut ghost: Query<&mut Visibility, With<BuildMenuItem>>| {
+            |mut ghost: Query<&mut Visibility, With<BuildMenuItem>>,
+             mut relocating: ResMut<Relocating>| {
                 for mut build_menu_item in &mut ghost {
                     *build_menu_item = Visibility::Hidden;
                 }
+                relocating.0 = None;
             },
         )
-        .add_systems(FixedUpdate, on_build.run_if(in_state(InMenu::BuildMenu)))
+        .add_systems(
+            FixedUpdate,
+            on_build.run_if(in_state(InMenu::BuildMenu).and(in_state(InGameState::Running))),
+        )
         .add_systems(
             OnEnter(GameState::InGame),
-            (spawn_ghost, spawn_blueprint_window),
+            (
+                spawn_ghost,
+                (spawn_blueprint_window, fill_blueprint_window).chain(),
+                spawn_storage_warning,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                fill_blueprint_window,
+                update_ghost,
+                apply_definition_changes,
+            )
+                .run_if(
+                    in_state(GameState::InGame).and(on_event::<AssetEvent<BuildingDefinition>>),
+                ),
         )
         .add_systems(
             FixedUpdate,
@@ -128,99 +188,176 @@ pub fn build_plugin(app: &mut App) {
                     .and(in_state(InGameState::Running))
                     .and(in_state(TrainState::Advancing)),
             ),
+        )
+        .add_systems(
+            Update,
+            update_storage_warning.run_if(in_state(GameState::InGame)),
         );
 }
 
+#[derive(Component)]
+struct StorageWarning;
+
+fn spawn_storage_warning(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(GameState::InGame),
+        StorageWarning,
+        Visibility::Hidden,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            justify_self: JustifySelf::Center,
+            padding: UiRect::all(Val::Px(5.0)),
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+        children![(
+            Text::new("Storage full, production paused"),
+            TextColor(Color::srgb(1.0, 0.2, 0.2)),
+        )],
+    ));
+}
+
+fn update_storage_warning(
+    productions: Query<&ResourceProduction>,
+    mut warning: Single<&mut Visibility, With<StorageWarning>>,
+) {
+    **warning = if productions.iter().any(|it| it.output_blocked) {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+}
+
 #[derive(Component)]
 struct BuildMenuItem;
 
-fn spawn_ghost(mut commands: Commands, image_assets: Res<ImageAssets>) {
+fn spawn_ghost(mut commands: Commands, building_type: Res<BuildingType>, buildings: Buildings) {
     commands.spawn((
+        StateScoped(GameState::InGame),
         Visibility::Hidden,
         BuildMenuItem,
         GhostBuilding,
-        Sprite::from_image(image_assets.farm.clone()),
+        Sprite::from_image(
+            buildings
+                .get(&building_type)
+                .map(|it| it.texture.clone())
+                .unwrap_or_default(),
+        ),
         Transform::from_xyz(0., 0., 5.0),
     ));
 }
 
 fn update_ghost(
-    mut ghost: Query<&mut Sprite, With<GhostBuilding>>,
+    mut ghost: Single<&mut Sprite, With<GhostBuilding>>,
     building_type: Res<BuildingType>,
-    image_assets: Res<ImageAssets>,
+    buildings: Buildings,
 ) {
-    let mut ghost = ghost.single_mut().unwrap();
-    ghost.image = building_type.get_texture(&image_assets);
+    if let Some(definition) = buildings.get(&building_type) {
+        ghost.image = definition.texture.clone();
+    }
 }
 
 #[derive(Component)]
 struct BluePrintButton(BuildingType);
 
-fn spawn_blueprint_window(
+#[derive(Component, Clone)]
+struct BluePrintCost(BuildingType);
+
+pub(crate) fn format_cost(cost: &[(Item, usize)], items: &ItemRegistry) -> String {
+    cost.iter()
+        .map(|(item, amount)| format!("{}x{}", items.name(item), amount))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Component)]
+struct BlueprintWindow;
+
+fn spawn_blueprint_window(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(GameState::InGame),
+        Visibility::Hidden,
+        BuildMenuItem,
+        BlueprintWindow,
+        Node {
+            top: Val::Vh(5.0),
+            right: Val::Px(0.),
+            display: Display::Flex,
+            position_type: PositionType::Absolute,
+            justify_content: JustifyContent::End,
+            align_items: AlignItems::FlexEnd,
+            flex_direction: FlexDirection::Column,
+            flex_wrap: FlexWrap::WrapReverse,
+            max_height: Val::Vh(85.0),
+            padding: UiRect::all(Val::Px(10.0)),
+            margin: UiRect::top(Val::Px(10.0)),
+            ..Default::default()
+        },
+    ));
+}
+
+/// Lists every building, again whenever their definitions change.
+fn fill_blueprint_window(
+    window: Single<Entity, With<BlueprintWindow>>,
+    buildings: Buildings,
+    items: Items,
     mut commands: Commands,
-    image_assets: Res<ImageAssets>,
-    mut texture_atlases: ResMut<Assets<TextureAtlasLayout>>,
 ) {
-    let texture_atlas =
-        TextureAtlasLayout::from_grid(UVec2::splat(80), 1, 1, None, Some(UVec2::new(200, 60)));
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
-    let texture_atlas = TextureAtlas::from(texture_atlas_handle);
     commands
-        .spawn((
-            Visibility::Hidden,
-            BuildMenuItem,
-            Node {
-                top: Val::Vh(5.0),
-                right: Val::Px(0.),
-                display: Display::Flex,
-                position_type: PositionType::Absolute,
-                justify_content: JustifyContent::End,
-                align_items: AlignItems::FlexEnd,
-                flex_direction: FlexDirection::Column,
-                padding: UiRect::all(Val::Px(10.0)),
-                margin: UiRect::top(Val::Px(10.0)),
-                ..Default::default()
-            },
-        ))
+        .entity(*window)
+        .despawn_related::<Children>()
         .with_children(|parent| {
-            for building_type in BuildingType::iterator() {
-                parent.spawn((
-                    Node {
-                        width: Val::Px(142.0),
-                        display: Display::Flex,
-                        flex_direction: FlexDirection::Column,
-                        justify_content: JustifyContent::Center,
-                        ..Default::default()
-                    },
-                    children![
-                        (
-                            ImageNode::from_atlas_image(
-                                building_type.get_texture(&*image_assets),
-                                texture_atlas.clone(),
+            for definition in buildings.iter() {
+                parent
+                    .spawn((
+                        Node {
+                            width: Val::Px(142.0),
+                            display: Display::Flex,
+                            flex_direction: FlexDirection::Column,
+                            justify_content: JustifyContent::Center,
+                            ..Default::default()
+                        },
+                        children![
+                            (
+                                ImageNode {
+                                    rect: Some(definition.footprint),
+                                    ..ImageNode::new(definition.texture.clone())
+                                },
+                                Node {
+                                    width: Val::Px(142.0),
+                                    height: Val::Px(142.0),
+                                    bottom: Val::Px(0.0),
+
+                                    ..Default::default()
+                                },
+                                BluePrintButton(definition.id.clone()),
+                                Button,
                             ),
-                            Node {
-                                width: Val::Px(142.0),
-                                height: Val::Px(142.0),
-                                bottom: Val::Px(0.0),
-
-                                ..Default::default()
-                            },
-                            BluePrintButton(building_type),
-                            Button,
-                        ),
-                        (
-                            Node {
-                                width: Val::Percent(100.0),
-                                display: Display::Flex,
-                                align_items: AlignItems::Center,
-                                justify_content: JustifyContent::Center,
-
-                                ..default()
-                            },
-                            children![Text::new(building_type.name()),],
-                        ),
-                    ],
-                ));
+                            (
+                                Node {
+                                    width: Val::Percent(100.0),
+                                    display: Display::Flex,
+                                    align_items: AlignItems::Center,
+                                    justify_content: JustifyContent::Center,
+
+                                    ..default()
+                                },
+                                children![Text::new(definition.name.clone()),],
+                            ),
+                        ],
+                    ))
+                    .with_children(|parent| {
+                        spawn_items(
+                            parent,
+                            &items,
+                            &definition.cost,
+                            (
+                                TextFont::from_font_size(12.0),
+                                BluePrintCost(definition.id.clone()),
+                            ),
+                        );
+                    });
             }
         });
 }
@@ -231,14 +368,45 @@ fn change_selected_building(
         (Changed<Interaction>, With<Button>),
     >,
     mut current_building: ResMut<BuildingType>,
+    mut relocating: ResMut<Relocating>,
 ) {
     for (interaction, BluePrintButton(building_type)) in &interaction_query {
         if *interaction == Interaction::Pressed {
-            *current_building = *building_type;
+            *current_building = building_type.clone();
+            relocating.0 = None;
         }
     }
 }
 
+const UNAFFORDABLE_COLOR: Color = Color::srgb(0.4, 0.4, 0.4);
+
+fn update_blueprint_affordability(
+    inventories: Query<&Inventory>,
+    mut blueprints: Query<(&BluePrintButton, &mut ImageNode)>,
+    mut costs: Query<(&BluePrintCost, &mut TextColor)>,
+    buildings: Buildings,
+) {
+    let affordable = |building_type| {
+        buildings
+            .get(building_type)
+            .is_some_and(|it| can_afford(&inventories, &it.cost))
+    };
+    for (BluePrintButton(building_type), mut image) in &mut blueprints {
+        image.color = if affordable(building_type) {
+            Color::WHITE
+        } else {
+            UNAFFORDABLE_COLOR
+        };
+    }
+    for (BluePrintCost(building_type), mut text_color) in &mut costs {
+        text_color.0 = if affordable(building_type) {
+            Color::WHITE
+        } else {
+            UNAFFORDABLE_COLOR
+        };
+    }
+}
+
 pub const MAX_CONSTRUCTION_SNAPPING: f32 = 40.0;
 
 #[derive(Event)]
@@ -257,10 +425,27 @@ fn construct_buildings(
     mut ev: EventWriter<BuildEvent>,
     mut commands: Commands,
     building_type: Res<BuildingType>,
+    buildings: Buildings,
+    mut inventories: Query<&mut Inventory>,
+    mut relocating: ResMut<Relocating>,
+    placed: Query<(&Transform, &ChildOf), With<Building>>,
+    ancestors: Query<&ChildOf>,
+    mut next_state: ResMut<NextState<InMenu>>,
 ) {
     let Ok((camera, camera_transform)) = q_camera.single() else {
         return;
     };
+    let Some(definition) = buildings.get(&building_type) else {
+        return;
+    };
+    // the building being moved was destroyed in the meantime, so there is nothing to place
+    if let Some(moving) = relocating.0
+        && placed.get(moving).is_err()
+    {
+        info!("The building being moved is gone");
+        relocating.0 = None;
+        return;
+    }
     if let Some(position) = window
         .cursor_position()
         .and_then(|position| camera.viewport_to_world_2d(camera_transform, position).ok())
@@ -269,6 +454,18 @@ fn construct_buildings(
 
         let mut closest: Option<(f32, Entity, &BuildLocation, &GlobalTransform, &ChildOf)> = None;
         for (build_entity, build_location, build_transform, build_parent) in build_locations {
+            if !definition.fits(build_location.1) {
+                continue;
+            }
+            // a building can't be moved onto itself or anything stacked on it
+            if let Some(moving) = relocating.0
+                && (build_parent.parent() == moving
+                    || ancestors
+                        .iter_ancestors(build_parent.parent())
+                        .any(|it| it == moving))
+            {
+                continue;
+            }
             let closest_distance = closest
                 .map(|(distance, _, _, _, _)| distance)
                 .unwrap_or(MAX_CONSTRUCTION_SNAPPING.squared());
@@ -284,16 +481,40 @@ fn construct_buildings(
                 ));
             }
         }
+        let cost = &definition.cost;
+        let affordable = relocating.0.is_some() || can_afford(&inventories.as_readonly(), cost);
         if let Some((_, build_entity, build_location, build_transform, build_parent)) = closest {
-            ghost_sprite.color = Color::srgb(0.0, 1., 0.);
+            ghost_sprite.color = if affordable {
+                Color::srgb(0.0, 1., 0.)
+            } else {
+                Color::srgb(1.0, 0., 0.)
+            };
             ghost_transform.translation =
                 build_transform.translation() + build_location.0.extend(5.0);
-            if buttons.just_pressed(MouseButton::Left) {
+            if buttons.just_pressed(MouseButton::Left)
+                && let Some(moving) = relocating.0
+                && let Ok((transform, child_of)) = placed.get(moving)
+            {
+                commands.entity(build_entity).despawn();
+                commands.queue(RestoreBuildLocation {
+                    parent: child_of.parent(),
+                    offset: transform.translation.xy(),
+                });
+                commands.entity(moving).insert((
+                    ChildOf(build_parent.parent()),
+                    Transform::from_translation(build_location.0.extend(4.0)),
+                ));
+                relocating.0 = None;
+                next_state.set(InMenu::None);
+            } else if buttons.just_pressed(MouseButton::Left) && affordable {
+                for (item, amount) in cost {
+                    take_items(&mut inventories, item, *amount);
+                }
                 commands.entity(build_entity).despawn();
                 ev.write(BuildEvent {
                     child_of: build_parent.0,
                     offset: build_location.0,
-                    building_type: *building_type,
+                    building_type: building_type.clone(),
                 });
             }
         } else {
@@ -303,10 +524,12 @@ fn construct_buildings(
     }
 }
 
+/// New housing fills up with recruits straight away when built where the train can hire.
 fn on_build(
     mut ev: EventReader<BuildEvent>,
     parents: Query<Entity, With<Transform>>,
-    image_assets: Res<ImageAssets>,
+    buildings: Buildings,
+    current_stop: Res<CurrentStop>,
     mut commands: Commands,
 ) {
     for BuildEvent {
@@ -316,62 +539,245 @@ fn on_build(
     } in ev.read()
     {
         let parent = parents.get(*child_of).unwrap();
-        let mut building = commands.spawn((
-            Sprite::from_image(building_type.get_texture(&image_assets)),
-            Transform::from_translation(offset.extend(4.0)),
-            Building(*building_type),
-            // children![(BuildLocation(Vec2::new(0., 40.)), Transform::default())],
-            //
-            Pickable::default(),
-        ));
-        if let Some(resource_production) = building_type.get_resource_production() {
-            building.insert(resource_production);
-        }
-        building.with_children(|parent| {
-            for build_location in building_type.get_build_locations() {
-                parent.spawn((BuildLocation(build_location), Transform::default()));
-            }
-        });
-        match building_type {
-            BuildingType::Storage => {
-                building.insert(Inventory::default());
-            }
-            _ => {}
+        let Some(definition) = buildings.get(building_type) else {
+            continue;
+        };
+        let building = spawn_building(&mut commands, definition, parent, *offset, 0);
+        if definition.is_housing() && can_hire(&current_stop) {
+            commands.queue(MoveIn(building));
         }
+    }
+}
 
-        let building_id = building.id();
-        building.observe(
-            move |mut trigger: Trigger<Pointer<Click>>,
-                  mut selected_building: ResMut<BuildingInspected>,
-                  mut menu_state: ResMut<NextState<InMenu>>,
-                  current_menu_state: Res<State<InMenu>>| {
-                if let InMenu::None = **current_menu_state {
-                    println!("got click");
-                    selected_building.0 = Some(building_id);
-                    menu_state.set(InMenu::BuildingMenu);
-                    trigger.propagate(false);
-                }
-            },
-        );
-        commands.entity(parent).add_child(building_id);
+pub fn spawn_building(
+    commands: &mut Commands,
+    definition: &BuildingDefinition,
+    parent: Entity,
+    offset: Vec2,
+    level: usize,
+) -> Entity {
+    let tier = definition.tier(level);
+    let mut building = commands.spawn((
+        Sprite {
+            color: tier.tint(),
+            ..Sprite::from_image(definition.tier_texture(level))
+        },
+        Transform::from_translation(offset.extend(4.0)),
+        Building(definition.id.clone()),
+        BuildingTier(level),
+        Health::new(definition.health * tier.health),
+        // children![(BuildLocation(Vec2::new(0., 40.)), Transform::default())],
+        //
+        Pickable::default(),
+    ));
+    if let Some(recipe) = &definition.production {
+        let mut resource_production = ResourceProduction::new(recipe.clone());
+        resource_production.set_tier(tier);
+        building.insert(resource_production);
+    }
+    if let Some(stats) = &definition.turret {
+        building.insert(Turret::new(stats.clone()));
+    }
+    building.with_children(|parent| {
+        for (build_location, slot) in &definition.slots {
+            parent.spawn((BuildLocation(*build_location, *slot), Transform::default()));
+        }
+    });
+    if definition.is_storage() {
+        building.insert((Inventory::default(), Capacity(tier.capacity)));
     }
+
+    building.observe(inspect_on_click);
+    let building_id = building.id();
+    commands.entity(parent).add_child(building_id);
+    building_id
 }
 
+/// A finished cycle waits with its outputs until there is room for all of them on the train.
 fn produce_resources(
-    mut buildings: Query<&mut ResourceProduction>,
-    mut inventories: Query<&mut Inventory>,
+    mut buildings: Query<(&Building, &mut ResourceProduction, Option<&Workers>)>,
+    mut holds: Holds,
     time: Res<Time>,
+    definitions: Buildings,
 ) {
-    let mut produced_items = HashMap::new();
-    for mut building in &mut buildings {
-        if building.0.tick(time.delta()).just_finished() {
-            *produced_items.entry(building.1.clone()).or_insert(0) += 1;
+    for (building, mut production, workers) in &mut buildings {
+        let crew_needed = definitions.get(&building.0).map_or(0, |it| it.crew);
+        let production = production.as_mut();
+        production.understaffed = staffed(workers) < crew_needed;
+        if production.understaffed {
+            continue;
+        }
+        let recipe = &production.recipe;
+        if !production.running {
+            let missing = recipe.inputs.iter().find(|(item, amount)| {
+                total_owned(holds.iter().map(|(_, it, ..)| it), item) < *amount
+            });
+            if let Some((item, _)) = missing {
+                production.stalled_on = Some(item.clone());
+                continue;
+            }
+            for (item, amount) in &recipe.inputs {
+                take_items(holds.iter_mut().map(|(_, it, ..)| it), item, *amount);
+            }
+            production.running = true;
+            production.stalled_on = None;
         }
+        if production.timer.tick(time.delta()).finished() {
+            production.output_blocked = !store_all(&mut holds, &recipe.outputs);
+            if production.output_blocked {
+                continue;
+            }
+            production.timer.reset();
+            production.running = false;
+        }
+    }
+}
+
+/// Frees up the spot a building stood on so something else can be built there.
+pub struct RestoreBuildLocation {
+    pub parent: Entity,
+    pub offset: Vec2,
+}
+
+impl Command for RestoreBuildLocation {
+    fn apply(self, world: &mut World) {
+        let locations = if let Some(TrainCar(car_type)) = world.get::<TrainCar>(self.parent) {
+            car_type.get_build_locations()
+        } else if let Some(Building(building_type)) = world.get::<Building>(self.parent) {
+            let Some(definition) = definition(world, building_type) else {
+                return;
+            };
+            definition.slots.clone()
+        } else {
+            return;
+        };
+        let Some((offset, slot)) = locations
+            .into_iter()
+            .find(|(offset, _)| *offset == self.offset)
+        else {
+            return;
+        };
+        world
+            .entity_mut(self.parent)
+            .with_child((BuildLocation(offset, slot), Transform::default()));
     }
-    for (item, amount) in produced_items {
-        for mut inventory in &mut inventories {
-            *inventory.items.entry(item.clone()).or_insert(0) += amount;
-            break;
+}
+
+/// Brings the buildings already on the train in line with a definition edited while the game
+/// runs, adding or removing production, turrets and storage as the definition now has them.
+/// Changes to `fits` only apply to buildings placed from then on. A building newly listed in
+/// `assets/buildings.ron` joins the build menu as soon as its file loads.
+fn apply_definition_changes(
+    mut events: EventReader<AssetEvent<BuildingDefinition>>,
+    definitions: Res<Assets<BuildingDefinition>>,
+    mut buildings: Query<(
+        Entity,
+        &Building,
+        &BuildingTier,
+        &mut Sprite,
+        &mut Health,
+        Option<&mut ResourceProduction>,
+        Option<&mut Turret>,
+        Has<Inventory>,
+        Option<&Children>,
+    )>,
+    build_locations: Query<(), With<BuildLocation>>,
+    stacked: Query<&Transform, With<Building>>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        let Some(definition) = definitions.get(*id) else {
+            continue;
+        };
+        info!("Reloaded the {} definition", definition.name);
+        for (
+            entity,
+            building,
+            BuildingTier(level),
+            mut sprite,
+            mut health,
+            production,
+            turret,
+            has_inventory,
+            children,
+        ) in &mut buildings
+        {
+            if building.0 != definition.id {
+                continue;
+            }
+            let tier = definition.tier(*level);
+            sprite.image = definition.tier_texture(*level);
+            sprite.color = tier.tint();
+
+            let max_health = definition.health * tier.health;
+            health.current *= max_health / health.max;
+            health.max = max_health;
+
+            match (production, &definition.production) {
+                (Some(mut production), Some(recipe)) => {
+                    production.recipe = recipe.clone();
+                    production.set_tier(tier);
+                }
+                (None, Some(recipe)) => {
+                    let mut production = ResourceProduction::new(recipe.clone());
+                    production.set_tier(tier);
+                    commands.entity(entity).insert(production);
+                }
+                (Some(_), None) => {
+                    commands.entity(entity).remove::<ResourceProduction>();
+                }
+                (None, None) => {}
+            }
+            match (turret, &definition.turret) {
+                (Some(mut turret), Some(stats)) => turret.stats = stats.clone(),
+                (None, Some(stats)) => {
+                    commands.entity(entity).insert(Turret::new(stats.clone()));
+                }
+                (Some(_), None) => {
+                    commands.entity(entity).remove::<Turret>();
+                }
+                (None, None) => {}
+            }
+            if definition.is_storage() {
+                commands.entity(entity).insert(Capacity(tier.capacity));
+                if !has_inventory {
+                    commands.entity(entity).insert(Inventory::default());
+                }
+            } else if has_inventory {
+                // whatever it holds stays put until it is moved out or the building demolished
+                commands.entity(entity).insert(Capacity(0));
+            }
+
+            // free build locations are laid out again, the ones in use stay taken
+            let children = children.into_iter().flatten().copied().collect::<Vec<_>>();
+            let taken = children
+                .iter()
+                .filter_map(|it| stacked.get(*it).ok())
+                .map(|it| it.translation.xy())
+                .collect::<Vec<_>>();
+            for child in &children {
+                if build_locations.contains(*child) {
+                    commands.entity(*child).despawn();
+                }
+            }
+            for offset in &taken {
+                if !definition.slots.iter().any(|(it, _)| it == offset) {
+                    warn!(
+                        "A building stacked on a {} sits where it has no slot any more",
+                        definition.name
+                    );
+                }
+            }
+            commands.entity(entity).with_children(|parent| {
+                for (offset, slot) in &definition.slots {
+                    if !taken.contains(offset) {
+                        parent.spawn((BuildLocation(*offset, *slot), Transform::default()));
+                    }
+                }
+            });
         }
     }
 }
@@ -0,0 +1,13 @@
+use serde::Deserialize;
+
+use crate::resources_plugin::Item;
+
+/// One production cycle: `inputs` are taken from the train when the cycle starts and
+/// `outputs` are delivered once `seconds` have passed.
+#[derive(Deserialize, Clone)]
+pub struct Recipe {
+    #[serde(default)]
+    pub inputs: Vec<(Item, usize)>,
+    pub outputs: Vec<(Item, usize)>,
+    pub seconds: f32,
+}
@@ -0,0 +1,61 @@
+use bevy::{asset::LoadContext, prelude::*};
+use serde::Deserialize;
+
+use crate::resources_plugin::Item;
+
+/// One level of a building, the first of each type being what gets built.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Tier {
+    /// What upgrading to this tier costs, empty for the first tier.
+    pub upgrade_cost: Vec<(Item, usize)>,
+    /// Multiplies how long a production cycle takes.
+    pub production_time: f32,
+    /// How many units the building holds, zero for buildings without an inventory.
+    pub capacity: usize,
+    /// Beds before any car bonus, for housing.
+    pub beds: usize,
+    /// Multiplies the building's health.
+    pub health: f32,
+    /// Red, green and blue the sprite is tinted with.
+    tint: (f32, f32, f32),
+    /// Replaces the building's texture from this tier up, laid out like the original.
+    texture: Option<String>,
+    #[serde(skip)]
+    image: Option<Handle<Image>>,
+}
+
+impl Default for Tier {
+    fn default() -> Self {
+        Self {
+            upgrade_cost: Vec::new(),
+            production_time: 1.0,
+            capacity: 0,
+            beds: 0,
+            health: 1.0,
+            tint: (1.0, 1.0, 1.0),
+            texture: None,
+            image: None,
+        }
+    }
+}
+
+impl Tier {
+    pub fn tint(&self) -> Color {
+        let (red, green, blue) = self.tint;
+        Color::srgb(red, green, blue)
+    }
+
+    /// The texture this tier brings in, if it has its own.
+    pub fn image(&self) -> Option<&Handle<Image>> {
+        self.image.as_ref()
+    }
+
+    pub(super) fn load_texture(&mut self, load_context: &mut LoadContext) {
+        self.image = self.texture.clone().map(|it| load_context.load(it));
+    }
+}
+
+/// How far a building has been upgraded, as an index into its tiers.
+#[derive(Component, Clone, Copy, Default)]
+pub struct BuildingTier(pub usize);
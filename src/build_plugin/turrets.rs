@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    GameState, InGameState,
+    combat_plugin::Health,
+    goblins::Goblin,
+    resources_plugin::{Inventory, Item, take_items, total_owned},
+};
+
+use super::{Building, definitions::Buildings, tiers::BuildingTier};
+
+/// How a kind of turret shoots, shared by every turret of that [`BuildingType`](super::BuildingType).
+#[derive(Deserialize, Clone)]
+pub struct TurretStats {
+    pub range: f32,
+    pub damage: f32,
+    pub reload_seconds: f32,
+    /// What the turret shoots, taken from the train's inventories.
+    pub ammo: Item,
+    /// Units of [`Self::ammo`] taken for every shot.
+    pub ammo_per_shot: usize,
+    pub projectile_speed: f32,
+}
+
+const PROJECTILE_HIT_RADIUS: f32 = 15.0;
+const OUT_OF_AMMO_COLOR: Color = Color::srgb(0.4, 0.4, 0.4);
+
+#[derive(Component)]
+pub struct Turret {
+    pub stats: TurretStats,
+    pub reload: Timer,
+    pub kills: usize,
+    /// Set while the train has too little ammo for another shot.
+    pub out_of_ammo: bool,
+}
+
+impl Turret {
+    pub fn new(stats: TurretStats) -> Self {
+        Self {
+            reload: Timer::new(
+                Duration::from_secs_f32(stats.reload_seconds),
+                TimerMode::Once,
+            ),
+            stats,
+            kills: 0,
+            out_of_ammo: false,
+        }
+    }
+}
+
+#[derive(Component)]
+struct Projectile {
+    velocity: Vec2,
+    damage: f32,
+    /// The turret that fired it, credited with the kill.
+    turret: Entity,
+    /// Despawned once it has flown the turret's range without hitting anything.
+    range_left: f32,
+}
+
+pub fn turrets_plugin(app: &mut App) {
+    app.add_systems(
+        FixedUpdate,
+        (fire_turrets, move_projectiles)
+            .chain()
+            .run_if(in_state(GameState::InGame).and(in_state(InGameState::Running))),
+    )
+    .add_systems(Update, show_out_of_ammo.run_if(in_state(GameState::InGame)));
+}
+
+fn fire_turrets(
+    mut turrets: Query<(Entity, &mut Turret, &GlobalTransform)>,
+    goblins: Query<&GlobalTransform, With<Goblin>>,
+    mut inventories: Query<&mut Inventory>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut turret, transform) in &mut turrets {
+        // only running out of ammo and firing mark the turret changed, not every tick of
+        // the reload, so `show_out_of_ammo` has something to go on
+        turret.bypass_change_detection().reload.tick(time.delta());
+        let ammo_per_shot = turret.stats.ammo_per_shot;
+        let out_of_ammo =
+            total_owned(inventories.as_readonly(), &turret.stats.ammo) < ammo_per_shot;
+        if turret.out_of_ammo != out_of_ammo {
+            turret.out_of_ammo = out_of_ammo;
+        }
+        if !turret.reload.finished() || turret.out_of_ammo {
+            continue;
+        }
+
+        let position = transform.translation().xy();
+        let Some(target) = goblins
+            .iter()
+            .map(|it| it.translation().xy())
+            .filter(|it| it.distance(position) <= turret.stats.range)
+            .min_by(|a, b| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            })
+        else {
+            continue;
+        };
+
+        take_items(&mut inventories, &turret.stats.ammo, ammo_per_shot);
+
+        commands.spawn((
+            StateScoped(GameState::InGame),
+            Projectile {
+                velocity: (target - position).normalize_or_zero() * turret.stats.projectile_speed,
+                damage: turret.stats.damage,
+                turret: entity,
+                range_left: turret.stats.range,
+            },
+            Sprite::from_color(Color::srgb(1.0, 0.9, 0.2), Vec2::splat(6.0)),
+            Transform::from_translation(position.extend(30.0)),
+        ));
+        turret.reload.reset();
+    }
+}
+
+fn move_projectiles(
+    mut projectiles: Query<(Entity, &mut Projectile, &mut Transform)>,
+    mut goblins: Query<(&mut Health, &GlobalTransform), With<Goblin>>,
+    mut turrets: Query<&mut Turret>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut projectile, mut transform) in &mut projectiles {
+        let step = projectile.velocity * time.delta_secs();
+        transform.translation += step.extend(0.0);
+        projectile.range_left -= step.length();
+
+        let position = transform.translation.xy();
+        let hit = goblins.iter_mut().find(|(health, goblin)| {
+            !health.is_dead()
+                && goblin.translation().xy().distance(position) <= PROJECTILE_HIT_RADIUS
+        });
+        if let Some((mut health, _)) = hit {
+            health.damage(projectile.damage);
+            if health.is_dead()
+                && let Ok(mut turret) = turrets.get_mut(projectile.turret)
+            {
+                turret.kills += 1;
+            }
+            commands.entity(entity).despawn();
+        } else if projectile.range_left <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Greys out turrets that have nothing left to shoot, and gives them their tier's tint back
+/// once there is ammo again.
+fn show_out_of_ammo(
+    mut turrets: Query<(&Turret, &Building, &BuildingTier, &mut Sprite), Changed<Turret>>,
+    definitions: Buildings,
+) {
+    for (turret, building, BuildingTier(level), mut sprite) in &mut turrets {
+        let greyed_out = sprite.color == OUT_OF_AMMO_COLOR;
+        if turret.out_of_ammo == greyed_out {
+            continue;
+        }
+        sprite.color = if turret.out_of_ammo {
+            OUT_OF_AMMO_COLOR
+        } else {
+            definitions
+                .get(&building.0)
+                .map_or(Color::WHITE, |it| it.tier(*level).tint())
+        };
+    }
+}
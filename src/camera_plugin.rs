@@ -15,6 +15,7 @@ pub fn camera_plugin(app: &mut App) {
     app.init_resource::<CameraSpeeds>();
     app.add_systems(Update, move_camera.run_if(in_state(GameState::InGame)));
     app.add_systems(Startup, spawn_camera);
+    app.add_systems(OnExit(GameState::InGame), reset_camera);
 }
 
 fn spawn_camera(mut commands: Commands) {
@@ -33,6 +34,14 @@ fn spawn_camera(mut commands: Commands) {
     ));
 }
 
+fn reset_camera(
+    mut camera: Single<&mut Transform, With<Camera>>,
+    mut camera_speeds: ResMut<CameraSpeeds>,
+) {
+    camera.translation = Vec3::new(0., 300.0, 0.);
+    *camera_speeds = CameraSpeeds::default();
+}
+
 const CAMERA_MOVE_SPEED: f32 = 120.0;
 const CAMERA_MAX_SPEED: f32 = 500.0;
 const CAMERA_ACCELERATION: f32 = 10000.0;
@@ -0,0 +1,270 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::{
+    GameState, InGameState,
+    build_plugin::{Building, RestoreBuildLocation},
+    crew_plugin::Rehouse,
+    goblins::Goblin,
+    train_plugin::{CAR_SIZE, Caboose, Locomotive, TrainCar, TrainState, TrainStats},
+    ui_state::InMenu,
+    world_plugin::{CurrentStop, NumberedStop, Stop},
+};
+
+pub const LOCOMOTIVE_HEALTH: f32 = 100.0;
+
+const HEALTH_BAR_WIDTH: f32 = 60.0;
+const HEALTH_BAR_HEIGHT: f32 = 6.0;
+const HEALTH_BAR_OFFSET: f32 = 70.0;
+
+#[derive(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    pub fn damage(&mut self, amount: f32) {
+        self.current = (self.current - amount).max(0.0);
+    }
+}
+
+/// Why the run ended, shown on the game over screen.
+#[derive(Resource, Default, Clone, Copy)]
+pub enum Defeat {
+    #[default]
+    LocomotiveDestroyed,
+    /// The boiler ran dry between stops and the train coasted to a halt.
+    Stranded,
+}
+
+impl Defeat {
+    fn message(&self) -> &'static str {
+        match self {
+            Defeat::LocomotiveDestroyed => "THE LOCOMOTIVE WAS DESTROYED",
+            Defeat::Stranded => "THE TRAIN RAN OUT OF FUEL",
+        }
+    }
+}
+
+#[derive(Component)]
+struct HealthBar;
+
+#[derive(Component)]
+struct GameOverButton;
+
+pub fn combat_plugin(app: &mut App) {
+    app.init_resource::<Defeat>()
+        .add_systems(
+            Update,
+            (add_health_bars, update_health_bars).run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(
+            FixedUpdate,
+            (destroy_buildings, wreck_cars, check_defeat)
+                .run_if(in_state(GameState::InGame).and(in_state(InGameState::Running))),
+        )
+        .add_systems(
+            OnEnter(TrainState::Stopped),
+            repair_at_towns.run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(OnEnter(InGameState::Defeated), spawn_game_over_screen)
+        .add_systems(
+            Update,
+            game_over_button.run_if(in_state(InGameState::Defeated)),
+        );
+}
+
+fn add_health_bars(
+    healths: Query<Entity, (Added<Health>, Without<Goblin>)>,
+    mut commands: Commands,
+) {
+    for entity in healths {
+        commands.entity(entity).with_child((
+            HealthBar,
+            Sprite {
+                color: Color::srgb(0.8, 0.1, 0.1),
+                custom_size: Some(Vec2::new(HEALTH_BAR_WIDTH, HEALTH_BAR_HEIGHT)),
+                anchor: Anchor::CenterLeft,
+                ..Default::default()
+            },
+            Transform::from_xyz(-HEALTH_BAR_WIDTH / 2.0, HEALTH_BAR_OFFSET, 20.0),
+            Visibility::Hidden,
+        ));
+    }
+}
+
+/// Only damaged things show a bar, a fully repaired train stays uncluttered.
+fn update_health_bars(
+    healths: Query<(&Health, &Children), Changed<Health>>,
+    mut bars: Query<(&mut Transform, &mut Visibility), With<HealthBar>>,
+) {
+    for (health, children) in healths {
+        let mut bars = bars.iter_many_mut(children);
+        while let Some((mut transform, mut visibility)) = bars.fetch_next() {
+            transform.scale.x = health.current / health.max;
+            *visibility = if health.current < health.max {
+                Visibility::Visible
+            } else {
+                Visibility::Hidden
+            };
+        }
+    }
+}
+
+/// A destroyed building takes whatever is stacked on it down with it, whoever lived there moves
+/// to other housing, see [`Rehouse`].
+fn destroy_buildings(
+    buildings: Query<(Entity, &Health, &Transform, &ChildOf), With<Building>>,
+    children: Query<&Children>,
+    mut commands: Commands,
+) {
+    for (entity, health, transform, child_of) in buildings {
+        if !health.is_dead() {
+            continue;
+        }
+        info!("A building was destroyed");
+        let stack = std::iter::once(entity)
+            .chain(children.iter_descendants(entity))
+            .filter(|it| buildings.contains(*it))
+            .collect();
+        commands.queue(Rehouse(stack));
+        commands.entity(entity).despawn();
+        commands.queue(RestoreBuildLocation {
+            parent: child_of.parent(),
+            offset: transform.translation.xy(),
+        });
+    }
+}
+
+/// A destroyed car takes everything on it down with it, the cars behind are coupled up to close
+/// the gap. Crew living on the car move to other housing, see [`Rehouse`].
+fn wreck_cars(
+    mut parts: Query<
+        (Entity, &mut Transform, Option<&Health>, Has<TrainCar>),
+        Or<(With<TrainCar>, With<Caboose>)>,
+    >,
+    buildings: Query<(), With<Building>>,
+    children: Query<&Children>,
+    mut train_stats: ResMut<TrainStats>,
+    mut commands: Commands,
+) {
+    let mut wrecked = parts
+        .iter()
+        .filter(|(_, _, health, is_car)| *is_car && health.is_some_and(Health::is_dead))
+        .map(|(entity, transform, _, _)| (entity, transform.translation.x))
+        .collect::<Vec<_>>();
+    // back to front, so the positions of the cars still to be wrecked stay put
+    wrecked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    for (car, x) in wrecked {
+        info!("A car was wrecked");
+        let index = (x / CAR_SIZE).round() as usize - 1;
+        if index < train_stats.cars.len() {
+            train_stats.cars.remove(index);
+        }
+        let wreckage = std::iter::once(car)
+            .chain(
+                children
+                    .iter_descendants(car)
+                    .filter(|it| buildings.contains(*it)),
+            )
+            .collect();
+        commands.queue(Rehouse(wreckage));
+        commands.entity(car).despawn();
+        for (_, mut transform, _, _) in &mut parts {
+            if transform.translation.x > x {
+                transform.translation.x -= CAR_SIZE;
+            }
+        }
+    }
+}
+
+fn check_defeat(
+    locomotive: Query<&Health, (With<Locomotive>, Changed<Health>)>,
+    mut next_state: ResMut<NextState<InGameState>>,
+    mut defeat: ResMut<Defeat>,
+) {
+    for health in locomotive {
+        if health.is_dead() {
+            info!("The locomotive was destroyed");
+            *defeat = Defeat::LocomotiveDestroyed;
+            next_state.set(InGameState::Defeated);
+        }
+    }
+}
+
+fn repair_at_towns(
+    current_stop: Res<CurrentStop>,
+    mut healths: Query<&mut Health, Without<Goblin>>,
+) {
+    if let Some(NumberedStop(Stop::Town, _)) = current_stop.0 {
+        for mut health in &mut healths {
+            health.current = health.max;
+        }
+    }
+}
+
+fn spawn_game_over_screen(mut commands: Commands, defeat: Res<Defeat>) {
+    commands.spawn((
+        StateScoped(InGameState::Defeated),
+        Node {
+            width: Val::Vw(100.0),
+            height: Val::Vh(100.0),
+            position_type: PositionType::Absolute,
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgba(0.2, 0., 0., 0.8)),
+        GlobalZIndex(10),
+        children![
+            (
+                Text::new(defeat.message()),
+                TextFont {
+                    font_size: 64.0,
+                    ..Default::default()
+                }
+            ),
+            (
+                Button,
+                GameOverButton,
+                Node {
+                    width: Val::Px(240.0),
+                    height: Val::Px(40.0),
+                    display: Display::Flex,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    margin: UiRect::top(Val::Px(10.0)),
+                    ..Default::default()
+                },
+                BackgroundColor(Color::WHITE),
+                children![(Text::new("Main Menu"), TextColor(Color::BLACK))],
+            )
+        ],
+    ));
+}
+
+fn game_over_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<GameOverButton>)>,
+    mut in_game_state: ResMut<NextState<InGameState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut menu_state: ResMut<NextState<InMenu>>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            in_game_state.set(InGameState::Running);
+            menu_state.set(InMenu::None);
+            game_state.set(GameState::MainMenu);
+        }
+    }
+}
@@ -2,9 +2,10 @@ use bevy::prelude::*;
 
 use crate::{
     GameState, InGameState,
-    train_plugin::{AdvanceEvent, Train},
+    resources_plugin::Inventory,
+    train_plugin::{AdvanceEvent, Locomotive, Train, fuel::Boiler},
     ui_state::InMenu,
-    world_plugin::NextStop,
+    world_plugin::{GameWorld, NextStop},
 };
 
 pub fn control_panel_plugin(app: &mut App) {
@@ -14,6 +15,7 @@ pub fn control_panel_plugin(app: &mut App) {
             (
                 advance_button,
                 build_button.run_if(in_state(InMenu::None).or(in_state(InMenu::BuildMenu))),
+                menu_toggles,
             )
                 .run_if(in_state(GameState::InGame))
                 .run_if(in_state(InGameState::Running)),
@@ -41,7 +43,10 @@ pub fn control_panel_plugin(app: &mut App) {
             OnExit(InMenu::BuildMenu),
             |mut build_button: Query<(&mut BuildButton, &Children)>,
              mut text_query: Query<&mut Text>| {
-                let (mut build_button, children) = build_button.single_mut().unwrap();
+                // the control panel is already gone when leaving the game mid-build
+                let Ok((mut build_button, children)) = build_button.single_mut() else {
+                    return;
+                };
                 *build_button = BuildButton::StartBuilding;
                 let mut text = text_query.get_mut(children[0]).unwrap();
                 **text = "Build".to_string();
@@ -51,14 +56,18 @@ pub fn control_panel_plugin(app: &mut App) {
 
 #[derive(Component)]
 struct AdvanceButton;
+/// Opens a full menu, or closes it again when it is already open.
+#[derive(Component)]
+struct MenuToggle(InMenu);
 #[derive(Component)]
 enum BuildButton {
     StartBuilding,
     EndBuilding,
 }
 
-fn spawn_control_panel(mut commands: Commands) {
+fn spawn_control_panel(mut commands: Commands, world: Res<GameWorld>) {
     commands.spawn((
+        StateScoped(GameState::InGame),
         Node {
             width: Val::Vw(100.0),
             height: Val::Vh(5.0),
@@ -87,6 +96,35 @@ fn spawn_control_panel(mut commands: Commands) {
                 children![Text::new("Advance")]
             ),
             (NextTownDisplay, Text::new("Next town: {}")),
+            (
+                Node {
+                    width: Val::Px(120.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                BackgroundColor(Color::srgb(0.5, 0.5, 0.5)),
+                BorderRadius::MAX,
+                MenuToggle(InMenu::RouteMap),
+                Button,
+                children![Text::new("Map")]
+            ),
+            (
+                Node {
+                    width: Val::Px(120.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                BackgroundColor(Color::srgb(0.5, 0.5, 0.5)),
+                BorderRadius::MAX,
+                MenuToggle(InMenu::Journal),
+                Button,
+                children![Text::new("Journal")]
+            ),
+            Text::new(format!("Seed: {}", world.seed)),
             (
                 Node {
                     width: Val::Px(180.0),
@@ -118,6 +156,8 @@ fn advance_button(
         (Changed<Interaction>, With<Button>, With<AdvanceButton>),
     >,
     blockers: Query<(&AdvanceBlocker, Option<&Visibility>)>,
+    boiler: Single<&Boiler, With<Locomotive>>,
+    inventories: Query<&Inventory>,
     mut ev: EventWriter<AdvanceEvent>,
 ) {
     for interaction in &interaction_query {
@@ -127,6 +167,11 @@ fn advance_button(
                     .iter()
                     .all(|(_, it)| matches!(it, Some(Visibility::Hidden))))
         {
+            // the fuel gauge already shows why, setting off would only strand the train
+            if boiler.range(inventories.iter()) <= 0.0 {
+                warn!("Not advancing, the train has nothing to burn");
+                continue;
+            }
             info!("Sending advance event");
             ev.write(AdvanceEvent);
         }
@@ -147,6 +192,26 @@ fn build_button(
     }
 }
 
+fn menu_toggles(
+    interaction_query: Query<(&Interaction, &MenuToggle), Changed<Interaction>>,
+    menu_state: Res<State<InMenu>>,
+    mut next_state: ResMut<NextState<InMenu>>,
+) {
+    for (interaction, MenuToggle(menu)) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if menu_state.get() == menu {
+            next_state.set(InMenu::None);
+        } else if matches!(
+            menu_state.get(),
+            InMenu::None | InMenu::RouteMap | InMenu::Journal
+        ) {
+            next_state.set(menu.clone());
+        }
+    }
+}
+
 fn update_next_town_display(
     mut next_town_display: Query<&mut Text, With<NextTownDisplay>>,
     next_stop: Res<NextStop>,
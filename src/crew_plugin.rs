@@ -0,0 +1,287 @@
+use bevy::{ecs::relationship::RelationshipTarget, prelude::*};
+use rand::seq::IndexedRandom;
+
+use crate::{
+    GameState, InGameState,
+    build_plugin::{
+        Building,
+        definitions::{Buildings, definition},
+        tiers::{BuildingTier, Tier},
+    },
+    resources_plugin::{Inventory, Item, take_items},
+    train_plugin::{TrainCar, TrainState},
+    world_plugin::{CurrentStop, GameWorld, NumberedStop, Stop},
+};
+
+/// Seconds between the crew's meals.
+const MEAL_INTERVAL: f32 = 30.0;
+/// Morale lost for each of Food and Water missing at a meal.
+const HUNGER_MORALE_LOSS: f32 = 0.25;
+/// Morale regained with every full meal.
+const FED_MORALE_GAIN: f32 = 0.1;
+
+/// Crew are drawn as plain figures this size, standing in a row in front of their housing.
+const CREW_SIZE: Vec2 = Vec2::new(8.0, 16.0);
+const CREW_SPACING: f32 = 12.0;
+const CREW_COLOR: Color = Color::srgb(0.35, 0.25, 0.2);
+
+const FIRST_NAMES: &[&str] = &[
+    "Ada", "Bert", "Cora", "Dmitri", "Edna", "Fergus", "Greta", "Horace", "Ivy", "Jonah", "Kit",
+    "Lottie", "Mabel", "Ned", "Olive", "Percy", "Rosa", "Silas", "Tilly", "Wilf",
+];
+const SURNAMES: &[&str] = &[
+    "Ashdown",
+    "Blackwood",
+    "Coalby",
+    "Dunmore",
+    "Fenwick",
+    "Gravel",
+    "Hobbs",
+    "Ironside",
+    "Marsh",
+    "Pennyworth",
+    "Sallow",
+    "Thatcher",
+];
+
+#[derive(Component)]
+pub struct CrewMember {
+    pub name: String,
+    /// From 0 to 1, the crew member leaves the train once it runs out.
+    pub morale: f32,
+}
+
+/// The housing a crew member sleeps in.
+#[derive(Component)]
+#[relationship(relationship_target = Occupants)]
+pub struct LivesIn(pub Entity);
+
+#[derive(Component)]
+#[relationship_target(relationship = LivesIn, linked_spawn)]
+pub struct Occupants(Vec<Entity>);
+
+/// The production building a crew member is assigned to.
+#[derive(Component)]
+#[relationship(relationship_target = Workers)]
+pub struct WorksAt(pub Entity);
+
+#[derive(Component)]
+#[relationship_target(relationship = WorksAt)]
+pub struct Workers(Vec<Entity>);
+
+/// How many recruits have joined this run, so each gets their own name from the seed.
+#[derive(Resource, Default)]
+pub struct Recruited(pub usize);
+
+#[derive(Resource)]
+struct MealTimer(Timer);
+
+impl Default for MealTimer {
+    fn default() -> Self {
+        Self(Timer::from_seconds(MEAL_INTERVAL, TimerMode::Repeating))
+    }
+}
+
+/// Beds in a housing of the given tier standing on `car`, which is `None` for housing stacked
+/// on a building.
+pub fn beds(tier: &Tier, car: Option<&TrainCar>) -> usize {
+    tier.beds + car.map_or(0, |TrainCar(car_type)| car_type.housing_bonus())
+}
+
+/// How many assigned crew `workers` comes to.
+pub fn staffed(workers: Option<&Workers>) -> usize {
+    workers.map_or(0, |it| it.len())
+}
+
+fn recruit_name(world: &GameWorld, recruit: usize) -> String {
+    let mut rng = world.recruit_rng(recruit);
+    format!(
+        "{} {}",
+        FIRST_NAMES.choose(&mut rng).unwrap(),
+        SURNAMES.choose(&mut rng).unwrap()
+    )
+}
+
+/// Whether the train is somewhere it can take on recruits, a town or the depot the run
+/// starts from.
+pub fn can_hire(current_stop: &CurrentStop) -> bool {
+    matches!(
+        current_stop.0,
+        Some(NumberedStop(Stop::Town | Stop::Initial, _))
+    )
+}
+
+/// Fills the free beds of a housing with new recruits. Only queued where the train
+/// [`can_hire`], or to give crew to saves from before there were any.
+pub struct MoveIn(pub Entity);
+
+impl Command for MoveIn {
+    fn apply(self, world: &mut World) {
+        let Some(parent) = world.get::<ChildOf>(self.0).map(ChildOf::parent) else {
+            return;
+        };
+        let (Some(Building(building_type)), Some(BuildingTier(level))) = (
+            world.get::<Building>(self.0),
+            world.get::<BuildingTier>(self.0),
+        ) else {
+            return;
+        };
+        let Some(definition) = definition(world, building_type) else {
+            return;
+        };
+        let beds = beds(definition.tier(*level), world.get::<TrainCar>(parent));
+        let occupied = world.get::<Occupants>(self.0).map_or(0, |it| it.len());
+        for _ in occupied..beds {
+            let mut recruited = world.resource_mut::<Recruited>();
+            recruited.0 += 1;
+            let recruit = recruited.0;
+            let name = recruit_name(world.resource::<GameWorld>(), recruit);
+            info!("{name} joined the crew");
+            world.spawn((
+                StateScoped(GameState::InGame),
+                CrewMember { name, morale: 1.0 },
+                LivesIn(self.0),
+            ));
+        }
+    }
+}
+
+/// Moves everyone living in the given buildings into free beds in the train's other housing,
+/// ahead of those buildings being torn down. Whoever finds no bed leaves the train.
+pub struct Rehouse(pub Vec<Entity>);
+
+impl Command for Rehouse {
+    fn apply(self, world: &mut World) {
+        let residents = self
+            .0
+            .iter()
+            .filter_map(|it| world.get::<Occupants>(*it))
+            .flat_map(|it| it.iter())
+            .collect::<Vec<_>>();
+        if residents.is_empty() {
+            return;
+        }
+        let mut housing = world.query::<(Entity, &Building, &BuildingTier, &ChildOf)>();
+        let mut free_beds = housing
+            .iter(world)
+            .filter(|(entity, ..)| !self.0.contains(entity))
+            .filter_map(
+                |(entity, Building(building_type), BuildingTier(level), child_of)| {
+                    let definition = definition(world, building_type)?;
+                    let beds = beds(
+                        definition.tier(*level),
+                        world.get::<TrainCar>(child_of.parent()),
+                    );
+                    let occupied = world.get::<Occupants>(entity).map_or(0, |it| it.len());
+                    (beds > occupied).then_some((entity, beds - occupied))
+                },
+            )
+            .collect::<Vec<_>>();
+        for resident in residents {
+            let name = world
+                .get::<CrewMember>(resident)
+                .map_or(String::new(), |it| it.name.clone());
+            match free_beds.iter_mut().find(|(_, free)| *free > 0) {
+                Some((home, free)) => {
+                    *free -= 1;
+                    world.entity_mut(resident).insert(LivesIn(*home));
+                    info!("{name} moved into other housing");
+                }
+                None => {
+                    warn!("{name} had nowhere left to sleep and left the train");
+                    world.despawn(resident);
+                }
+            }
+        }
+    }
+}
+
+pub fn crew_plugin(app: &mut App) {
+    app.init_resource::<Recruited>()
+        .init_resource::<MealTimer>()
+        .add_systems(
+            OnEnter(TrainState::Stopped),
+            recruit_at_towns.run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(
+            Update,
+            feed_crew.run_if(in_state(GameState::InGame).and(in_state(InGameState::Running))),
+        )
+        .add_systems(Update, place_crew.run_if(in_state(GameState::InGame)))
+        .add_systems(
+            OnExit(GameState::InGame),
+            |mut recruited: ResMut<Recruited>, mut timer: ResMut<MealTimer>| {
+                recruited.0 = 0;
+                timer.0.reset();
+            },
+        );
+}
+
+/// Towns have people looking for work, enough to fill every empty bed.
+fn recruit_at_towns(
+    current_stop: Res<CurrentStop>,
+    buildings: Query<(Entity, &Building)>,
+    definitions: Buildings,
+    mut commands: Commands,
+) {
+    if !can_hire(&current_stop) {
+        return;
+    }
+    for (entity, building) in &buildings {
+        if definitions
+            .get(&building.0)
+            .is_some_and(|it| it.is_housing())
+        {
+            commands.queue(MoveIn(entity));
+        }
+    }
+}
+
+/// Every crew member eats a unit of Food and drinks a unit of Water per meal. Going without
+/// wears their morale down until they walk off the train.
+fn feed_crew(
+    mut timer: ResMut<MealTimer>,
+    time: Res<Time>,
+    mut crew: Query<(Entity, &mut CrewMember)>,
+    mut inventories: Query<&mut Inventory>,
+    mut commands: Commands,
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    for (entity, mut member) in &mut crew {
+        let missed = [Item::FOOD, Item::WATER]
+            .iter()
+            .map(|item| take_items(inventories.iter_mut(), item, 1))
+            .sum::<usize>();
+        if missed == 0 {
+            member.morale = (member.morale + FED_MORALE_GAIN).min(1.0);
+            continue;
+        }
+        member.morale -= HUNGER_MORALE_LOSS * missed as f32;
+        warn!(
+            "{} went without a meal, morale is down to {:.0}%",
+            member.name,
+            member.morale.max(0.0) * 100.0
+        );
+        if member.morale <= 0.0 {
+            warn!("{} had enough and left the train", member.name);
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Lines the residents of a housing up in front of it whenever someone moves in or out. They
+/// are plain figures until `Human.blend` is rendered out to a sprite.
+fn place_crew(housing: Query<(Entity, &Occupants), Changed<Occupants>>, mut commands: Commands) {
+    for (home, occupants) in &housing {
+        let first = -CREW_SPACING * (occupants.len() as f32 - 1.0) / 2.0;
+        for (i, member) in occupants.iter().enumerate() {
+            commands.entity(member).insert((
+                ChildOf(home),
+                Sprite::from_color(CREW_COLOR, CREW_SIZE),
+                Transform::from_xyz(first + CREW_SPACING * i as f32, CREW_SIZE.y / 2.0, 1.0),
+            ));
+        }
+    }
+}
@@ -7,6 +7,7 @@ use crate::{
     build_plugin::{BuildLocation, MAX_CONSTRUCTION_SNAPPING},
     goblins::Goblin,
     resources_plugin::Item,
+    save_plugin::PendingLoad,
     world_plugin::stop_plugin::{ActiveContracts, Contract},
 };
 
@@ -19,7 +20,10 @@ const START_WITH_CONTRACT: bool = true;
 
 pub fn debug_plugin(app: &mut App) {
     if SKIP_MAIN_MENU {
-        app.add_systems(OnEnter(GameState::MainMenu), skip_main_menu);
+        app.add_systems(
+            OnEnter(GameState::MainMenu),
+            skip_main_menu.run_if(run_once),
+        );
     }
     if BUILD_LOCATION_GIZMO {
         app.add_systems(Update, build_location_gizmo);
@@ -31,7 +35,10 @@ pub fn debug_plugin(app: &mut App) {
         app.add_systems(Update, kill_goblins);
     }
     if START_WITH_CONTRACT {
-        app.add_systems(OnEnter(GameState::InGame), give_debug_contract);
+        app.add_systems(
+            OnEnter(GameState::InGame),
+            give_debug_contract.run_if(not(resource_exists::<PendingLoad>)),
+        );
     }
 }
 
@@ -90,8 +97,12 @@ fn kill_goblins(
 
 fn give_debug_contract(mut contracts: ResMut<ActiveContracts>) {
     contracts.0.push(Contract {
-        required: (Item::Wood, 5),
-        reward: (Item::Food, 5),
+        required: (Item::WOOD, 5),
+        reward: (Item::FOOD, 5),
         stop_number: 1,
+        destination: None,
+        issuer: None,
+        deposit: 0,
+        handed_over: 0,
     });
 }
@@ -1,4 +1,316 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 
+use crate::{
+    GameState, InGameState,
+    build_plugin::{Building, format_cost},
+    combat_plugin::Health,
+    resources_plugin::{Holds, Inventory, Item, Items, store_what_fits},
+    train_plugin::MaxPixelHeightOfTrain,
+};
+
+/// How hard the player hits a goblin by clicking on it.
+pub const CLICK_DAMAGE: f32 = 1.0;
+const ARROW_SPEED: f32 = 500.0;
+const ARROW_HIT_RADIUS: f32 = 10.0;
+/// How many items a saboteur grabs per attack.
+const STEAL_AMOUNT: usize = 5;
+/// A saboteur runs off once it carries this much.
+const SABOTEUR_CAPACITY: usize = 15;
+/// How far above the train a fleeing saboteur has to get to escape with its loot.
+const ESCAPE_HEIGHT: f32 = 600.0;
+/// Everything within this distance of a boss's target gets hit too.
+const BOSS_SPLASH_RADIUS: f32 = 80.0;
+
 #[derive(Component)]
 pub struct Goblin;
+
+/// Shared by every goblin, the numbers come from its
+/// [`GoblinType`](crate::world_plugin::goblin_spawner::GoblinType).
+#[derive(Component)]
+pub struct GoblinStats {
+    pub speed: f32,
+    pub damage: f32,
+    /// How close the goblin has to get to its target to attack.
+    pub reach: f32,
+    pub attack: Timer,
+}
+
+impl GoblinStats {
+    pub fn new(speed: f32, damage: f32, reach: f32, attack_interval: f32) -> Self {
+        Self {
+            speed,
+            damage,
+            reach,
+            attack: Timer::new(
+                Duration::from_secs_f32(attack_interval),
+                TimerMode::Repeating,
+            ),
+        }
+    }
+}
+
+/// Runs at the closest part of the train and hacks at it.
+#[derive(Component)]
+pub struct Brute;
+
+/// Keeps its distance and shoots arrows.
+#[derive(Component)]
+pub struct Archer;
+
+/// Climbs onto the roof and only goes after buildings.
+#[derive(Component)]
+pub struct Climber;
+
+/// Goes for the train's stores instead of its hull and runs off with what it grabs.
+#[derive(Component, Default)]
+pub struct Saboteur {
+    pub stolen: Vec<(Item, usize)>,
+}
+
+/// Slow, tough and hits everything around its target.
+#[derive(Component)]
+pub struct Boss;
+
+#[derive(Component)]
+struct Arrow {
+    target: Entity,
+    damage: f32,
+}
+
+pub fn goblins_plugin(app: &mut App) {
+    app.add_systems(
+        FixedUpdate,
+        (
+            (brutes, archers, climbers, saboteurs, bosses),
+            move_arrows,
+            kill_goblins,
+        )
+            .chain()
+            .run_if(in_state(GameState::InGame).and(in_state(InGameState::Running))),
+    );
+}
+
+/// Lets the player swat goblins by clicking them.
+pub fn hit_goblin(trigger: Trigger<Pointer<Click>>, mut goblins: Query<&mut Health, With<Goblin>>) {
+    if let Ok(mut health) = goblins.get_mut(trigger.target()) {
+        health.damage(CLICK_DAMAGE);
+    }
+}
+
+fn nearest<T>(position: Vec2, candidates: impl Iterator<Item = (T, Vec2)>) -> Option<(T, Vec2)> {
+    candidates.min_by(|(_, a), (_, b)| {
+        a.distance_squared(position)
+            .total_cmp(&b.distance_squared(position))
+    })
+}
+
+/// Walks towards `target` and returns true once it is close enough to attack, ticking the
+/// attack timer so the first swing comes a moment after arriving.
+fn approach(transform: &mut Transform, stats: &mut GoblinStats, target: Vec2, time: &Time) -> bool {
+    let offset = target - transform.translation.xy();
+    if offset.length() > stats.reach {
+        let step = offset.normalize() * stats.speed * time.delta_secs();
+        transform.translation += step.extend(0.0);
+        stats.attack.reset();
+        false
+    } else {
+        stats.attack.tick(time.delta()).just_finished()
+    }
+}
+
+fn brutes(
+    mut goblins: Query<(&mut Transform, &mut GoblinStats), With<Brute>>,
+    mut targets: Query<(Entity, &mut Health, &GlobalTransform), Without<Goblin>>,
+    time: Res<Time>,
+) {
+    for (mut transform, mut stats) in &mut goblins {
+        let Some((target, position)) = nearest(
+            transform.translation.xy(),
+            targets
+                .iter()
+                .filter(|(_, health, _)| !health.is_dead())
+                .map(|(entity, _, it)| (entity, it.translation().xy())),
+        ) else {
+            continue;
+        };
+        if approach(&mut transform, &mut stats, position, &time) {
+            targets.get_mut(target).unwrap().1.damage(stats.damage);
+        }
+    }
+}
+
+fn archers(
+    mut goblins: Query<(&mut Transform, &mut GoblinStats), With<Archer>>,
+    targets: Query<(Entity, &Health, &GlobalTransform), Without<Goblin>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (mut transform, mut stats) in &mut goblins {
+        let Some((target, position)) = nearest(
+            transform.translation.xy(),
+            targets
+                .iter()
+                .filter(|(_, health, _)| !health.is_dead())
+                .map(|(entity, _, it)| (entity, it.translation().xy())),
+        ) else {
+            continue;
+        };
+        if approach(&mut transform, &mut stats, position, &time) {
+            commands.spawn((
+                StateScoped(GameState::InGame),
+                Arrow {
+                    target,
+                    damage: stats.damage,
+                },
+                Sprite::from_color(Color::srgb(0.4, 0.25, 0.1), Vec2::new(8.0, 2.0)),
+                Transform::from_translation(transform.translation.with_z(30.0)),
+            ));
+        }
+    }
+}
+
+fn climbers(
+    mut goblins: Query<(&mut Transform, &mut GoblinStats), With<Climber>>,
+    mut buildings: Query<
+        (Entity, &mut Health, &GlobalTransform),
+        (With<Building>, Without<Goblin>),
+    >,
+    time: Res<Time>,
+) {
+    for (mut transform, mut stats) in &mut goblins {
+        let Some((target, position)) = nearest(
+            transform.translation.xy(),
+            buildings
+                .iter()
+                .filter(|(_, health, _)| !health.is_dead())
+                .map(|(entity, _, it)| (entity, it.translation().xy())),
+        ) else {
+            continue;
+        };
+        if approach(&mut transform, &mut stats, position, &time) {
+            buildings.get_mut(target).unwrap().1.damage(stats.damage);
+        }
+    }
+}
+
+fn saboteurs(
+    mut goblins: Query<(Entity, &mut Transform, &mut GoblinStats, &mut Saboteur)>,
+    mut stores: Query<(Entity, &mut Inventory, &GlobalTransform), Without<Goblin>>,
+    train_height: Res<MaxPixelHeightOfTrain>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (goblin, mut transform, mut stats, mut saboteur) in &mut goblins {
+        let carried = saboteur
+            .stolen
+            .iter()
+            .map(|(_, amount)| amount)
+            .sum::<usize>();
+        let store = nearest(
+            transform.translation.xy(),
+            stores
+                .iter()
+                .filter(|(_, inventory, _)| !inventory.is_empty())
+                .map(|(entity, _, it)| (entity, it.translation().xy())),
+        );
+
+        // run off once the pockets are full or there is nothing left worth taking
+        let Some((store, target)) = store.filter(|_| carried < SABOTEUR_CAPACITY) else {
+            if carried > 0 {
+                transform.translation.y += stats.speed * time.delta_secs();
+                if transform.translation.y > train_height.height + ESCAPE_HEIGHT {
+                    info!("A saboteur got away with {carried} items");
+                    commands.entity(goblin).despawn();
+                }
+            }
+            continue;
+        };
+        if approach(&mut transform, &mut stats, target, &time) {
+            let (_, mut inventory, _) = stores.get_mut(store).unwrap();
+            let Some((item, amount)) = inventory
+                .items
+                .iter_mut()
+                .filter(|(_, amount)| **amount > 0)
+                .max_by_key(|(_, amount)| **amount)
+            else {
+                continue;
+            };
+            let taken = (*amount).min(STEAL_AMOUNT);
+            *amount -= taken;
+            saboteur.stolen.push((item.clone(), taken));
+        }
+    }
+}
+
+fn bosses(
+    mut goblins: Query<(&mut Transform, &mut GoblinStats), With<Boss>>,
+    mut targets: Query<(&mut Health, &GlobalTransform), Without<Goblin>>,
+    time: Res<Time>,
+) {
+    for (mut transform, mut stats) in &mut goblins {
+        let Some((_, target)) = nearest(
+            transform.translation.xy(),
+            targets
+                .iter()
+                .filter(|(health, _)| !health.is_dead())
+                .map(|(_, it)| ((), it.translation().xy())),
+        ) else {
+            continue;
+        };
+        if approach(&mut transform, &mut stats, target, &time) {
+            for (mut health, position) in &mut targets {
+                if position.translation().xy().distance(target) <= BOSS_SPLASH_RADIUS {
+                    health.damage(stats.damage);
+                }
+            }
+        }
+    }
+}
+
+fn move_arrows(
+    mut arrows: Query<(Entity, &Arrow, &mut Transform)>,
+    mut targets: Query<(&mut Health, &GlobalTransform), Without<Arrow>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, arrow, mut transform) in &mut arrows {
+        let Ok((mut health, target)) = targets.get_mut(arrow.target) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        let offset = target.translation().xy() - transform.translation.xy();
+        if offset.length() <= ARROW_HIT_RADIUS {
+            health.damage(arrow.damage);
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let step = offset.clamp_length_max(ARROW_SPEED * time.delta_secs());
+        transform.translation += step.extend(0.0);
+        transform.rotation = Quat::from_rotation_z(offset.to_angle());
+    }
+}
+
+/// Dead saboteurs drop what they stole, which the crew stores on the train wherever it fits.
+fn kill_goblins(
+    goblins: Query<(Entity, &Health, Option<&Saboteur>), With<Goblin>>,
+    mut holds: Holds,
+    items: Items,
+    mut commands: Commands,
+) {
+    for (entity, health, saboteur) in goblins {
+        if health.is_dead() {
+            if let Some(saboteur) = saboteur {
+                let lost = store_what_fits(&mut holds, &saboteur.stolen, &[]);
+                if !lost.is_empty() {
+                    warn!(
+                        "No room on the train for {}, it was left behind",
+                        format_cost(&lost, &items)
+                    );
+                }
+            }
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -8,11 +8,15 @@ use ui_state::InMenu;
 
 mod build_plugin;
 mod camera_plugin;
+mod combat_plugin;
 mod control_panel_plugin;
+mod crew_plugin;
 mod debug_plugin;
 mod goblins;
 mod main_menu;
+mod pause_menu;
 mod resources_plugin;
+mod save_plugin;
 mod train_plugin;
 mod ui_state;
 mod world_plugin;
@@ -30,6 +34,8 @@ enum InGameState {
     #[default]
     Running,
     Paused,
+    /// The run is over, see [`combat_plugin::Defeat`] for why.
+    Defeated,
 }
 
 #[derive(AssetCollection, Resource)]
@@ -48,18 +54,12 @@ struct ImageAssets {
     goblin_stop_bg: Handle<Image>,
     #[asset(path = "goblinstop_fg.png")]
     goblin_stop_fg: Handle<Image>,
-    #[asset(path = "farm.png")]
-    farm: Handle<Image>,
     #[asset(path = "rail.png")]
     rail: Handle<Image>,
-    #[asset(path = "housing.png")]
-    housing: Handle<Image>,
     #[asset(path = "Contract.png")]
     contract: Handle<Image>,
     #[asset(path = "BoothCard.png")]
     booth_card: Handle<Image>,
-    #[asset(path = "DebugBuilding.png")]
-    debug_building: Handle<Image>,
     #[asset(path = "Ground.png")]
     ground: Handle<Image>,
     #[asset(path = "map_pin.png")]
@@ -124,15 +124,25 @@ fn main() {
         control_panel_plugin::control_panel_plugin,
         build_plugin::build_plugin,
         main_menu::main_menu_plugin,
+        pause_menu::pause_menu_plugin,
         resources_plugin::resources_plugin,
+        save_plugin::save_plugin,
+        combat_plugin::combat_plugin,
+        goblins::goblins_plugin,
+        crew_plugin::crew_plugin,
     ))
     .init_state::<InGameState>()
     .init_state::<GameState>()
     .init_state::<InMenu>()
+    .enable_state_scoped_entities::<GameState>()
+    .enable_state_scoped_entities::<InGameState>()
+    .enable_state_scoped_entities::<InMenu>()
     .add_loading_state(
         LoadingState::new(GameState::Loading)
             .continue_to_state(GameState::InGame)
             .load_collection::<ImageAssets>()
+            .load_collection::<build_plugin::definitions::BuildingAssets>()
+            .load_collection::<resources_plugin::registry::ItemAssets>()
             .load_collection::<FontAssets>(),
     );
     #[cfg(debug_assertions)]
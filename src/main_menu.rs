@@ -1,19 +1,48 @@
-use bevy::prelude::*;
+use bevy::{
+    input::{
+        ButtonState,
+        keyboard::{Key, KeyboardInput},
+    },
+    prelude::*,
+};
 
-use crate::GameState;
+use crate::{
+    GameState,
+    save_plugin::{PendingLoad, SAVE_PATH, read_save},
+    world_plugin::{WorldSeed, difficulty::Difficulty},
+};
 
 #[derive(Component)]
 struct MainMenu;
 #[derive(Component)]
 struct StartGame;
+#[derive(Component)]
+struct LoadGame;
+#[derive(Component)]
+struct SeedInput;
+#[derive(Component)]
+struct RandomSeed;
+#[derive(Component)]
+struct DifficultyButton;
 
 pub fn main_menu_plugin(app: &mut App) {
     app.add_systems(OnEnter(GameState::MainMenu), spawn_main_menu)
         .add_systems(OnExit(GameState::MainMenu), cleanup_main_menu)
-        .add_systems(Update, start_button.run_if(in_state(GameState::MainMenu)));
+        .add_systems(
+            Update,
+            (
+                start_button,
+                load_button,
+                random_seed_button,
+                type_seed,
+                update_seed_input.run_if(resource_changed::<WorldSeed>),
+                difficulty_button,
+            )
+                .run_if(in_state(GameState::MainMenu)),
+        );
 }
 
-fn spawn_main_menu(mut commands: Commands) {
+fn spawn_main_menu(mut commands: Commands, seed: Res<WorldSeed>, difficulty: Res<Difficulty>) {
     commands.spawn((
         MainMenu,
         Node {
@@ -38,6 +67,58 @@ fn spawn_main_menu(mut commands: Commands) {
                     ..Default::default()
                 }
             ),
+            (
+                Node {
+                    display: Display::Flex,
+                    align_items: AlignItems::Center,
+                    margin: UiRect::bottom(Val::Px(10.0)),
+                    ..Default::default()
+                },
+                children![
+                    Text::new("Seed: "),
+                    (
+                        SeedInput,
+                        Text::new(seed.0.to_string()),
+                        Node {
+                            min_width: Val::Px(200.0),
+                            margin: UiRect::right(Val::Px(10.0)),
+                            ..Default::default()
+                        }
+                    ),
+                    (
+                        Button,
+                        RandomSeed,
+                        Node {
+                            height: Val::Px(40.0),
+                            display: Display::Flex,
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::all(Val::Px(5.0)),
+                            ..Default::default()
+                        },
+                        BackgroundColor(Color::WHITE),
+                        children![(Text::new("Random"), TextColor(Color::BLACK))]
+                    )
+                ]
+            ),
+            (
+                Button,
+                DifficultyButton,
+                Node {
+                    height: Val::Px(40.0),
+                    display: Display::Flex,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::all(Val::Px(5.0)),
+                    margin: UiRect::bottom(Val::Px(10.0)),
+                    ..Default::default()
+                },
+                BackgroundColor(Color::WHITE),
+                children![(
+                    Text::new(format!("Difficulty: {}", difficulty.name())),
+                    TextColor(Color::BLACK)
+                )]
+            ),
             (
                 Button,
                 StartGame,
@@ -51,6 +132,21 @@ fn spawn_main_menu(mut commands: Commands) {
                 },
                 BackgroundColor(Color::WHITE),
                 children![(Text::new("Start Game"), TextColor(Color::BLACK))]
+            ),
+            (
+                Button,
+                LoadGame,
+                Node {
+                    height: Val::Px(40.0),
+                    display: Display::Flex,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::all(Val::Px(5.0)),
+                    margin: UiRect::top(Val::Px(10.0)),
+                    ..Default::default()
+                },
+                BackgroundColor(Color::WHITE),
+                children![(Text::new("Load Game"), TextColor(Color::BLACK))]
             )
         ],
     ));
@@ -88,3 +184,99 @@ fn start_button(
         }
     }
 }
+
+fn load_button(
+    mut interaction_query: Query<
+        (&Interaction, &Children, &mut BackgroundColor),
+        (Changed<Interaction>, With<Button>, With<LoadGame>),
+    >,
+    mut state: ResMut<NextState<GameState>>,
+    mut commands: Commands,
+    mut text_query: Query<&mut Text>,
+) {
+    for (interaction, children, mut background_color) in &mut interaction_query {
+        if *interaction == Interaction::Hovered {
+            background_color.0 = Color::srgb(0.85, 0.85, 0.85);
+        }
+        if *interaction == Interaction::None {
+            background_color.0 = Color::srgb(1., 1., 1.);
+        }
+        if *interaction == Interaction::Pressed {
+            match read_save(SAVE_PATH) {
+                Ok(save) => {
+                    commands.insert_resource(PendingLoad(save));
+                    state.set(GameState::Loading);
+                }
+                Err(err) => {
+                    warn!("Failed to load {SAVE_PATH}: {err}");
+                    let mut text = text_query.get_mut(children[0]).unwrap();
+                    **text = "No Save Found".to_string();
+                }
+            }
+        }
+    }
+}
+
+fn random_seed_button(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<Button>, With<RandomSeed>),
+    >,
+    mut seed: ResMut<WorldSeed>,
+) {
+    for (interaction, mut background_color) in &mut interaction_query {
+        if *interaction == Interaction::Hovered {
+            background_color.0 = Color::srgb(0.85, 0.85, 0.85);
+        }
+        if *interaction == Interaction::None {
+            background_color.0 = Color::srgb(1., 1., 1.);
+        }
+        if *interaction == Interaction::Pressed {
+            *seed = WorldSeed::random();
+        }
+    }
+}
+
+fn type_seed(mut keys: EventReader<KeyboardInput>, mut seed: ResMut<WorldSeed>) {
+    for key in keys.read() {
+        if key.state != ButtonState::Pressed {
+            continue;
+        }
+        match &key.logical_key {
+            Key::Character(c) if c.chars().all(|it| it.is_ascii_digit()) => {
+                if let Ok(new_seed) = format!("{}{c}", seed.0).parse() {
+                    seed.0 = new_seed;
+                }
+            }
+            Key::Backspace => seed.0 /= 10,
+            _ => {}
+        }
+    }
+}
+
+fn update_seed_input(mut seed_input: Single<&mut Text, With<SeedInput>>, seed: Res<WorldSeed>) {
+    ***seed_input = seed.0.to_string();
+}
+
+fn difficulty_button(
+    mut interaction_query: Query<
+        (&Interaction, &Children, &mut BackgroundColor),
+        (Changed<Interaction>, With<Button>, With<DifficultyButton>),
+    >,
+    mut difficulty: ResMut<Difficulty>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (interaction, children, mut background_color) in &mut interaction_query {
+        if *interaction == Interaction::Hovered {
+            background_color.0 = Color::srgb(0.85, 0.85, 0.85);
+        }
+        if *interaction == Interaction::None {
+            background_color.0 = Color::srgb(1., 1., 1.);
+        }
+        if *interaction == Interaction::Pressed {
+            *difficulty = difficulty.next();
+            let mut text = text_query.get_mut(children[0]).unwrap();
+            **text = format!("Difficulty: {}", difficulty.name());
+        }
+    }
+}
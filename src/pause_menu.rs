@@ -0,0 +1,207 @@
+use bevy::{
+    prelude::*,
+    window::{PrimaryWindow, WindowMode},
+};
+
+use crate::{
+    GameState, InGameState,
+    save_plugin::{SaveFinished, SaveGame},
+    ui_state::InMenu,
+};
+
+#[derive(Component)]
+struct PauseMenuPage;
+#[derive(Component)]
+struct SettingsPage;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum PauseButton {
+    Resume,
+    Settings,
+    Save,
+    Quit,
+    Fullscreen,
+    Back,
+}
+
+impl PauseButton {
+    fn label(&self) -> &'static str {
+        match self {
+            PauseButton::Resume => "Resume",
+            PauseButton::Settings => "Settings",
+            PauseButton::Save => "Save",
+            PauseButton::Quit => "Quit to Main Menu",
+            PauseButton::Fullscreen => "Toggle Fullscreen",
+            PauseButton::Back => "Back",
+        }
+    }
+}
+
+pub fn pause_menu_plugin(app: &mut App) {
+    app.add_systems(OnEnter(InGameState::Paused), spawn_pause_menu)
+        .add_systems(Update, toggle_pause.run_if(in_state(GameState::InGame)))
+        .add_systems(
+            Update,
+            (pause_buttons, show_save_result).run_if(in_state(InGameState::Paused)),
+        );
+}
+
+fn toggle_pause(
+    keys: Res<ButtonInput<KeyCode>>,
+    state: Res<State<InGameState>>,
+    mut next_state: ResMut<NextState<InGameState>>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        match **state {
+            InGameState::Running => next_state.set(InGameState::Paused),
+            InGameState::Paused => next_state.set(InGameState::Running),
+            InGameState::Defeated => {}
+        }
+    }
+}
+
+fn pause_menu_button(button: PauseButton) -> impl Bundle {
+    (
+        Button,
+        button,
+        Node {
+            width: Val::Px(240.0),
+            height: Val::Px(40.0),
+            display: Display::Flex,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            margin: UiRect::top(Val::Px(10.0)),
+            ..Default::default()
+        },
+        BackgroundColor(Color::WHITE),
+        children![(Text::new(button.label()), TextColor(Color::BLACK))],
+    )
+}
+
+fn spawn_pause_menu(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(InGameState::Paused),
+        Node {
+            width: Val::Vw(100.0),
+            height: Val::Vh(100.0),
+            position_type: PositionType::Absolute,
+            display: Display::Flex,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.7)),
+        GlobalZIndex(10),
+        children![
+            (
+                PauseMenuPage,
+                Node {
+                    display: Display::Flex,
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                children![
+                    (
+                        Text::new("PAUSED"),
+                        TextFont {
+                            font_size: 64.0,
+                            ..Default::default()
+                        }
+                    ),
+                    pause_menu_button(PauseButton::Resume),
+                    pause_menu_button(PauseButton::Settings),
+                    pause_menu_button(PauseButton::Save),
+                    pause_menu_button(PauseButton::Quit),
+                ]
+            ),
+            (
+                SettingsPage,
+                Node {
+                    display: Display::None,
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                children![
+                    (
+                        Text::new("SETTINGS"),
+                        TextFont {
+                            font_size: 64.0,
+                            ..Default::default()
+                        }
+                    ),
+                    pause_menu_button(PauseButton::Fullscreen),
+                    pause_menu_button(PauseButton::Back),
+                ]
+            ),
+        ],
+    ));
+}
+
+fn pause_buttons(
+    mut interaction_query: Query<
+        (&Interaction, &PauseButton, &mut BackgroundColor),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut pause_page: Single<&mut Node, (With<PauseMenuPage>, Without<SettingsPage>)>,
+    mut settings_page: Single<&mut Node, (With<SettingsPage>, Without<PauseMenuPage>)>,
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+    mut in_game_state: ResMut<NextState<InGameState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut menu_state: ResMut<NextState<InMenu>>,
+    mut ev: EventWriter<SaveGame>,
+) {
+    for (interaction, button, mut background_color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Hovered => background_color.0 = Color::srgb(0.85, 0.85, 0.85),
+            Interaction::None => background_color.0 = Color::srgb(1., 1., 1.),
+            Interaction::Pressed => match button {
+                PauseButton::Resume => in_game_state.set(InGameState::Running),
+                PauseButton::Settings => {
+                    pause_page.display = Display::None;
+                    settings_page.display = Display::Flex;
+                }
+                PauseButton::Save => {
+                    ev.write(SaveGame);
+                }
+                PauseButton::Quit => {
+                    in_game_state.set(InGameState::Running);
+                    menu_state.set(InMenu::None);
+                    game_state.set(GameState::MainMenu);
+                }
+                PauseButton::Fullscreen => {
+                    window.mode = match window.mode {
+                        WindowMode::Windowed => {
+                            WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+                        }
+                        _ => WindowMode::Windowed,
+                    };
+                }
+                PauseButton::Back => {
+                    pause_page.display = Display::Flex;
+                    settings_page.display = Display::None;
+                }
+            },
+        }
+    }
+}
+
+fn show_save_result(
+    mut ev: EventReader<SaveFinished>,
+    buttons: Query<(&PauseButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    for SaveFinished(result) in ev.read() {
+        for (button, children) in &buttons {
+            if *button != PauseButton::Save {
+                continue;
+            }
+            let mut text = text_query.get_mut(children[0]).unwrap();
+            **text = match result {
+                Ok(()) => "Saved".to_string(),
+                Err(_) => "Save Failed".to_string(),
+            };
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{GameState, InGameState, train_plugin::Locomotive};
+
+pub mod registry;
+
+pub use registry::{ItemCategory, ItemRegistry, Items, spawn_items};
+
+/// An item by the id it is listed under in `assets/items.ron`. What the item is like is in its
+/// [`ItemDefinition`](registry::ItemDefinition).
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Item(Cow<'static, str>);
+
+impl Item {
+    pub const FOOD: Self = Self::named("Food");
+    pub const WATER: Self = Self::named("Water");
+    pub const WOOD: Self = Self::named("Wood");
+    pub const COAL: Self = Self::named("Coal");
+    pub const CLAY: Self = Self::named("Clay");
+    pub const BRICK: Self = Self::named("Brick");
+    pub const METAL: Self = Self::named("Metal");
+    pub const GLASS: Self = Self::named("Glass");
+    /// What everything is paid for with.
+    pub const MONEY: Self = Self::named("Money");
+
+    pub const fn named(id: &'static str) -> Self {
+        Self(Cow::Borrowed(id))
+    }
+
+    pub fn id(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Component, Clone)]
+pub struct Inventory {
+    pub items: HashMap<Item, usize>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            items: HashMap::new(),
+        }
+    }
+}
+
+/// Limits an [`Inventory`] to the listed items, e.g. a tanker that only holds liquids.
+#[derive(Component, Clone)]
+pub struct AcceptedItems(pub Vec<Item>);
+
+impl AcceptedItems {
+    pub fn accepts(&self, item: &Item) -> bool {
+        self.0.contains(item)
+    }
+}
+
+/// The most an [`Inventory`] holds, counting every item together apart from
+/// [`Item::MONEY`], which takes no room. Inventories without one have no limit.
+#[derive(Component, Clone, Copy)]
+pub struct Capacity(pub usize);
+
+impl Inventory {
+    pub fn count(&self, item: &Item) -> usize {
+        self.items.get(item).cloned().unwrap_or(0)
+    }
+
+    pub fn weight(&self, items: &ItemRegistry) -> f32 {
+        self.items
+            .iter()
+            .map(|(item, amount)| items.weight(item) * *amount as f32)
+            .sum()
+    }
+
+    /// How many units take up room, see [`Capacity`].
+    pub fn total(&self) -> usize {
+        self.items
+            .iter()
+            .filter(|(item, _)| **item != Item::MONEY)
+            .map(|(_, amount)| amount)
+            .sum()
+    }
+
+    /// How many more units of `item` fit.
+    pub fn space_for(
+        &self,
+        item: &Item,
+        accepted: Option<&AcceptedItems>,
+        capacity: Option<&Capacity>,
+    ) -> usize {
+        if accepted.is_some_and(|it| !it.accepts(item)) {
+            return 0;
+        }
+        if *item == Item::MONEY {
+            return usize::MAX;
+        }
+        capacity.map_or(usize::MAX, |it| it.0.saturating_sub(self.total()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.keys().len() == 0 || self.items.values().all(|it| *it == 0)
+    }
+}
+
+/// How many of `item` are held across all of `inventories`.
+pub fn total_owned<'a>(inventories: impl IntoIterator<Item = &'a Inventory>, item: &Item) -> usize {
+    inventories.into_iter().map(|it| it.count(item)).sum()
+}
+
+pub fn can_afford<'a>(
+    inventories: impl IntoIterator<Item = &'a Inventory> + Clone,
+    cost: &[(Item, usize)],
+) -> bool {
+    cost.iter()
+        .all(|(item, amount)| total_owned(inventories.clone(), item) >= *amount)
+}
+
+/// Removes `amount` of `item` spread over `inventories`, emptying them in order.
+/// Returns how many could not be taken.
+pub fn take_items<'a>(
+    inventories: impl IntoIterator<Item = Mut<'a, Inventory>>,
+    item: &Item,
+    mut amount: usize,
+) -> usize {
+    for mut inventory in inventories {
+        if amount == 0 {
+            break;
+        }
+        let Some(owned) = inventory.items.get_mut(item) else {
+            continue;
+        };
+        let taken = (*owned).min(amount);
+        *owned -= taken;
+        amount -= taken;
+    }
+    amount
+}
+
+/// Every inventory on the train, with what limits it and where it sits for [`store_all`].
+pub type Holds<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Inventory,
+        Option<&'static AcceptedItems>,
+        Option<&'static Capacity>,
+        &'static GlobalTransform,
+        Has<Locomotive>,
+    ),
+>;
+
+/// Stores all of `items` on the train, or nothing at all if they don't all fit. Holds are
+/// filled front to back with the locomotive last, so it keeps room for fuel.
+pub fn store_all(holds: &mut Holds, items: &[(Item, usize)]) -> bool {
+    let (order, left_over) = fill(holds, items, &[]);
+    if !left_over.is_empty() {
+        return false;
+    }
+    write_back(holds, order);
+    true
+}
+
+/// Like [`store_all`], but stores whatever fits and leaves out the holds in `skip`. Returns
+/// what didn't fit.
+pub fn store_what_fits(
+    holds: &mut Holds,
+    items: &[(Item, usize)],
+    skip: &[Entity],
+) -> Vec<(Item, usize)> {
+    let (order, left_over) = fill(holds, items, skip);
+    write_back(holds, order);
+    left_over
+}
+
+/// Works out where `items` go on copies of the inventories, in fill order.
+fn fill(
+    holds: &Holds,
+    items: &[(Item, usize)],
+    skip: &[Entity],
+) -> (Vec<(Entity, Inventory)>, Vec<(Item, usize)>) {
+    let mut order = holds
+        .iter()
+        .filter(|(entity, ..)| !skip.contains(entity))
+        .map(
+            |(entity, inventory, accepted, capacity, transform, is_locomotive)| {
+                (
+                    entity,
+                    inventory.clone(),
+                    accepted.cloned(),
+                    capacity.copied(),
+                    (is_locomotive, transform.translation().x),
+                )
+            },
+        )
+        .collect::<Vec<_>>();
+    order.sort_by(|(.., a), (.., b)| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+    let mut left_over = Vec::new();
+    for (item, amount) in items {
+        let mut left = *amount;
+        for (_, inventory, accepted, capacity, _) in &mut order {
+            let stored = inventory
+                .space_for(item, accepted.as_ref(), capacity.as_ref())
+                .min(left);
+            if stored > 0 {
+                *inventory.items.entry(item.clone()).or_insert(0) += stored;
+                left -= stored;
+            }
+        }
+        if left > 0 {
+            left_over.push((item.clone(), left));
+        }
+    }
+    let order = order
+        .into_iter()
+        .map(|(entity, inventory, ..)| (entity, inventory))
+        .collect();
+    (order, left_over)
+}
+
+/// Only touches the inventories that changed, to keep change detection quiet.
+fn write_back(holds: &mut Holds, order: Vec<(Entity, Inventory)>) {
+    for (entity, stored) in order {
+        let (_, mut inventory, ..) = holds.get_mut(entity).unwrap();
+        if inventory.items != stored.items {
+            *inventory = stored;
+        }
+    }
+}
+
+/// Seconds between perishables going off.
+const SPOILAGE_INTERVAL: f32 = 60.0;
+
+#[derive(Resource)]
+struct SpoilageTimer(Timer);
+
+impl Default for SpoilageTimer {
+    fn default() -> Self {
+        Self(Timer::from_seconds(SPOILAGE_INTERVAL, TimerMode::Repeating))
+    }
+}
+
+pub fn resources_plugin(app: &mut App) {
+    app.init_asset::<ItemRegistry>()
+        .init_asset_loader::<registry::ItemRegistryLoader>()
+        .init_resource::<SpoilageTimer>()
+        .add_systems(
+            Update,
+            spoil_goods.run_if(in_state(GameState::InGame).and(in_state(InGameState::Running))),
+        )
+        .add_systems(
+            OnExit(GameState::InGame),
+            |mut timer: ResMut<SpoilageTimer>| timer.0.reset(),
+        );
+}
+
+/// Every [`SPOILAGE_INTERVAL`] perishables lose their
+/// [`spoilage`](registry::ItemDefinition::spoilage) share of whatever is held of them.
+fn spoil_goods(
+    mut timer: ResMut<SpoilageTimer>,
+    time: Res<Time>,
+    items: Items,
+    mut inventories: Query<&mut Inventory>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    for mut inventory in &mut inventories {
+        let spoiled = inventory
+            .items
+            .iter()
+            .filter_map(|(item, amount)| {
+                let spoilage = items.get(item)?.spoilage?;
+                let spoiled = ((*amount as f32 * spoilage).round() as usize).min(*amount);
+                (spoiled > 0).then(|| (item.clone(), spoiled))
+            })
+            .collect::<Vec<_>>();
+        for (item, spoiled) in spoiled {
+            *inventory.items.get_mut(&item).unwrap() -= spoiled;
+            info!("{spoiled} {} went off", items.name(&item));
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    ecs::system::SystemParam,
+    prelude::*,
+};
+use bevy_asset_loader::asset_collection::AssetCollection;
+use serde::Deserialize;
+
+use crate::world_plugin::reputation::Faction;
+
+use super::Item;
+
+/// Icons are drawn this many pixels across next to the item's text.
+const ICON_SIZE: f32 = 16.0;
+
+#[derive(AssetCollection, Resource)]
+pub struct ItemAssets {
+    #[asset(path = "items.ron")]
+    pub registry: Handle<ItemRegistry>,
+}
+
+/// Looks up items in the [`ItemRegistry`], as it is after any hot reload.
+#[derive(SystemParam)]
+pub struct Items<'w> {
+    assets: Res<'w, ItemAssets>,
+    registries: Res<'w, Assets<ItemRegistry>>,
+}
+
+impl std::ops::Deref for Items<'_> {
+    type Target = ItemRegistry;
+
+    fn deref(&self) -> &ItemRegistry {
+        self.registries.get(&self.assets.registry).unwrap()
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ItemCategory {
+    Solid,
+    /// Only these go in tankers.
+    Liquid,
+    Ammo,
+    /// Not traded at markets.
+    Currency,
+}
+
+pub struct ItemDefinition {
+    pub id: Item,
+    pub name: String,
+    pub icon: Handle<Image>,
+    /// Weight of a single unit in tonnes.
+    pub weight: f32,
+    /// What a unit goes for in [`Item::MONEY`] at a town with no particular need for it.
+    pub base_price: f32,
+    /// How many units change hands at once at a market.
+    pub stack_size: usize,
+    pub category: ItemCategory,
+    /// The share of the stock that goes off every minute, for perishables.
+    pub spoilage: Option<f32>,
+    /// Who runs the towns that make the item.
+    pub faction: Faction,
+}
+
+/// Every item there is, in the order they are listed in `assets/items.ron`.
+#[derive(Asset, TypePath)]
+pub struct ItemRegistry(Vec<ItemDefinition>);
+
+impl ItemRegistry {
+    pub fn get(&self, item: &Item) -> Option<&ItemDefinition> {
+        self.0.iter().find(|it| it.id == *item)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ItemDefinition> {
+        self.0.iter()
+    }
+
+    /// The ids of every item in `category`.
+    pub fn of_category(&self, category: ItemCategory) -> Vec<Item> {
+        self.iter()
+            .filter(|it| it.category == category)
+            .map(|it| it.id.clone())
+            .collect()
+    }
+
+    /// The display name of `item`, its id if it isn't in the registry.
+    pub fn name<'a>(&'a self, item: &'a Item) -> &'a str {
+        self.get(item).map_or(item.id(), |it| it.name.as_str())
+    }
+
+    pub fn weight(&self, item: &Item) -> f32 {
+        self.get(item).map_or(0.0, |it| it.weight)
+    }
+
+    pub fn base_price(&self, item: &Item) -> f32 {
+        self.get(item).map_or(0.0, |it| it.base_price)
+    }
+}
+
+/// Spawns each of `amounts` as the item's icon followed by "{name}x{amount}", with `text`
+/// added to every label.
+pub fn spawn_items(
+    parent: &mut ChildSpawnerCommands,
+    items: &ItemRegistry,
+    amounts: &[(Item, usize)],
+    text: impl Bundle + Clone,
+) {
+    for (item, amount) in amounts {
+        parent
+            .spawn(Node {
+                display: Display::Flex,
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(2.0),
+                ..Default::default()
+            })
+            .with_children(|parent| {
+                parent.spawn((
+                    ImageNode::new(
+                        items
+                            .get(item)
+                            .map(|it| it.icon.clone())
+                            .unwrap_or_default(),
+                    ),
+                    Node {
+                        width: Val::Px(ICON_SIZE),
+                        height: Val::Px(ICON_SIZE),
+                        ..Default::default()
+                    },
+                ));
+                parent.spawn((
+                    Text::new(format!("{}x{}", items.name(item), amount)),
+                    text.clone(),
+                ));
+            });
+    }
+}
+
+/// An entry of `items.ron` as written, before its icon is loaded.
+#[derive(Deserialize)]
+struct ItemEntry {
+    id: String,
+    name: String,
+    icon: String,
+    weight: f32,
+    base_price: f32,
+    stack_size: usize,
+    category: ItemCategory,
+    #[serde(default)]
+    spoilage: Option<f32>,
+    faction: Faction,
+}
+
+#[derive(Default)]
+pub struct ItemRegistryLoader;
+
+impl AssetLoader for ItemRegistryLoader {
+    type Asset = ItemRegistry;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<ItemRegistry, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let entries = ron::de::from_bytes::<Vec<ItemEntry>>(&bytes)?;
+        Ok(ItemRegistry(
+            entries
+                .into_iter()
+                .map(|entry| ItemDefinition {
+                    id: Item(entry.id.into()),
+                    name: entry.name,
+                    icon: load_context.load(entry.icon),
+                    weight: entry.weight,
+                    base_price: entry.base_price,
+                    stack_size: entry.stack_size,
+                    category: entry.category,
+                    spoilage: entry.spoilage,
+                    faction: entry.faction,
+                })
+                .collect(),
+        ))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["items.ron"]
+    }
+}
@@ -0,0 +1,766 @@
+use std::{fs, path::Path, time::Duration};
+
+use bevy::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    GameState, ImageAssets, InGameState,
+    build_plugin::{
+        BuildLocation, Building, BuildingType, ResourceProduction, definitions::Buildings,
+        spawn_building, tiers::BuildingTier, turrets::Turret,
+    },
+    combat_plugin::Health,
+    crew_plugin::{CrewMember, LivesIn, MoveIn, Recruited, WorksAt},
+    resources_plugin::{Inventory, Item},
+    train_plugin::{
+        Locomotive, Train, TrainCar, TrainState, TrainStats, car_type::CarType, fuel::Boiler,
+        starting_cargo,
+    },
+    world_plugin::{
+        CurrentStop, GameWorld, NextStop, NumberedStop, Stop, WorldSeed,
+        difficulty::Difficulty,
+        generate_world,
+        market::{Market, TownMarket},
+        progress_bar_plugin::LastStopDist,
+        reputation::Reputation,
+        route_map::RouteMap,
+        stop_plugin::{ActiveContracts, Contract},
+    },
+};
+
+pub const SAVE_PATH: &str = "saves/save.json";
+
+/// Bump this whenever [`SaveData`] changes shape and add a matching entry to
+/// [`MIGRATIONS`] that upgrades the previous version.
+pub const SAVE_VERSION: u32 = 17;
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` save to version `n + 2`.
+const MIGRATIONS: &[fn(&mut Value)] = &[
+    // every run used to start from the same hard-coded seed
+    |save| save["seed"] = 460.into(),
+    // the locomotive didn't carry anything before buildings had a cost
+    |save| {
+        save["locomotive_inventory"] =
+            serde_json::to_value(starting_cargo().items.into_iter().collect::<Vec<_>>()).unwrap()
+    },
+    // only farms produced anything and they had no inputs to hold on to
+    |save| {
+        for building in save["buildings"].as_array_mut().into_iter().flatten() {
+            building["production_running"] = false.into();
+        }
+    },
+    // every car was a plain boxcar
+    |save| {
+        let length = save["train_stats"]["length"].as_u64().unwrap_or(2);
+        save["cars"] = (0..length)
+            .map(|_| serde_json::json!({ "car_type": "Standard", "inventory": null }))
+            .collect();
+    },
+    // acceleration and top speed are worked out from the train's weight now
+    |save| {
+        if let Some(save) = save.as_object_mut() {
+            save.remove("train_stats");
+        }
+    },
+    // the locomotive ran without fuel
+    |save| {
+        save["train"]["fuel"] = 0.0.into();
+        save["train"]["water"] = 0.0.into();
+    },
+    // nothing could be damaged before goblins fought back
+    |save| {
+        save["train"]["locomotive_damage"] = 0.0.into();
+        for car in save["cars"].as_array_mut().into_iter().flatten() {
+            car["damage"] = 0.0.into();
+        }
+        for building in save["buildings"].as_array_mut().into_iter().flatten() {
+            building["damage"] = 0.0.into();
+        }
+    },
+    // there were no turrets to count kills for
+    |save| {
+        for building in save["buildings"].as_array_mut().into_iter().flatten() {
+            building["kills"] = 0.into();
+        }
+    },
+    // there was only one difficulty
+    |save| save["difficulty"] = "Normal".into(),
+    // the line never split, so the known route was just the next stop
+    |save| {
+        let current = match &save["current_stop"] {
+            Value::Null => serde_json::json!(["Initial", 0]),
+            stop => stop.clone(),
+        };
+        save["route"] = serde_json::json!({
+            "stops": [
+                {
+                    "id": 0,
+                    "stop": current,
+                    "distance": save["last_stop_distance"],
+                    "name": "",
+                    "next": [1],
+                },
+                {
+                    "id": 1,
+                    "stop": save["next_stop"]["stop"],
+                    "distance": save["next_stop"]["distance"],
+                    "name": save["next_stop"]["name"],
+                    "next": [],
+                },
+            ],
+            "current": 0,
+            "chosen": 1,
+            "next_id": 2,
+        });
+        for contract in save["contracts"].as_array_mut().into_iter().flatten() {
+            contract["destination"] = Value::Null;
+        }
+    },
+    // towns had no markets
+    |save| save["market"] = Value::Null,
+    // contracts were handed out by nobody in particular
+    |save| {
+        save["reputation"] = serde_json::json!({});
+        for contract in save["contracts"].as_array_mut().into_iter().flatten() {
+            contract["issuer"] = Value::Null;
+            contract["deposit"] = 0.into();
+        }
+    },
+    // housing stood empty and buildings worked on their own
+    |save| {
+        save["crew"] = Value::Null;
+        save["recruited"] = 0.into();
+    },
+    // buildings couldn't be upgraded
+    |save| {
+        for building in save["buildings"].as_array_mut().into_iter().flatten() {
+            building["tier"] = 0.into();
+        }
+    },
+    // buildings were variants of an enum, not named after their definition files
+    |save| {
+        for building in save["buildings"].as_array_mut().into_iter().flatten() {
+            let Some(name) = building["building_type"].as_str() else {
+                continue;
+            };
+            match LEGACY_BUILDING_TYPES.iter().find(|(old, _)| *old == name) {
+                Some((_, id)) => building["building_type"] = (*id).into(),
+                None => warn!("The save has a {name} in it, which isn't a building any more"),
+            }
+        }
+    },
+    // goods were only handed in on arrival
+    |save| {
+        for contract in save["contracts"].as_array_mut().into_iter().flatten() {
+            contract["handed_over"] = 0.into();
+        }
+    },
+];
+
+/// What each variant of the old building enum is called in `assets/buildings`.
+const LEGACY_BUILDING_TYPES: &[(&str, &str)] = &[
+    ("Housing", "housing"),
+    ("Farm", "farm"),
+    ("Storage", "storage"),
+    ("Kiln", "kiln"),
+    ("Smelter", "smelter"),
+    ("Glassworks", "glassworks"),
+    ("AmmoWorks", "ammo_works"),
+    ("GunTurret", "gun_turret"),
+    ("Cannon", "cannon"),
+];
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u32,
+    pub train: SavedTrain,
+    pub train_state: TrainState,
+    pub locomotive_inventory: Vec<(Item, usize)>,
+    /// Front to back.
+    pub cars: Vec<SavedCar>,
+    pub contracts: Vec<Contract>,
+    pub current_stop: Option<NumberedStop>,
+    pub next_stop: SavedNextStop,
+    pub route: RouteMap,
+    /// The market of the town the train is stopped at, with the prices the player has moved.
+    pub market: Option<Market>,
+    pub reputation: Reputation,
+    pub last_stop_distance: f32,
+    pub seed: u64,
+    pub rng: SavedRng,
+    pub difficulty: Difficulty,
+    /// Ordered so that every building comes after the building it sits on.
+    pub buildings: Vec<SavedBuilding>,
+    /// `None` for saves from before there was a crew, whose housing gets filled up on load.
+    pub crew: Option<Vec<SavedCrewMember>>,
+    pub recruited: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedCrewMember {
+    pub name: String,
+    pub morale: f32,
+    /// Index into [`SaveData::buildings`] of the housing they live in.
+    pub home: usize,
+    /// Index into [`SaveData::buildings`] of where they are assigned.
+    pub workplace: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedTrain {
+    pub distance: f32,
+    pub velocity: f32,
+    /// What is left in the [`Boiler`].
+    pub fuel: f32,
+    pub water: f32,
+    pub locomotive_damage: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedCar {
+    pub car_type: CarType,
+    pub inventory: Option<Vec<(Item, usize)>>,
+    /// How much [`Health`] the car has lost.
+    pub damage: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedNextStop {
+    pub stop: NumberedStop,
+    pub distance: f32,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedRng {
+    pub seed: [u8; 32],
+    pub stream: u64,
+    pub word_pos: u128,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedBuilding {
+    pub parent: SavedParent,
+    pub offset: [f32; 2],
+    pub building_type: BuildingType,
+    pub tier: usize,
+    pub inventory: Option<Vec<(Item, usize)>>,
+    pub production_elapsed: Option<f32>,
+    /// Whether the inputs of the current production cycle were already taken.
+    pub production_running: bool,
+    pub damage: f32,
+    /// Goblins shot down, for turrets.
+    pub kills: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum SavedParent {
+    /// Index of the car counting back from the locomotive.
+    Car(usize),
+    /// Index into [`SaveData::buildings`].
+    Building(usize),
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    UnknownVersion(u64),
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "{err}"),
+            SaveError::Json(err) => write!(f, "{err}"),
+            SaveError::UnknownVersion(version) => write!(f, "unknown save version {version}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(value: std::io::Error) -> Self {
+        SaveError::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(value: serde_json::Error) -> Self {
+        SaveError::Json(value)
+    }
+}
+
+/// A save picked from the main menu, applied once the run has been set up.
+#[derive(Resource)]
+pub struct PendingLoad(pub SaveData);
+
+#[derive(Event)]
+pub struct SaveGame;
+
+#[derive(Event)]
+pub struct SaveFinished(pub Result<(), String>);
+
+pub fn save_plugin(app: &mut App) {
+    app.add_event::<SaveGame>()
+        .add_event::<SaveFinished>()
+        .add_systems(
+            OnEnter(GameState::Loading),
+            restore_resources
+                .after(generate_world)
+                .run_if(resource_exists::<PendingLoad>),
+        )
+        .add_systems(
+            Update,
+            restore_entities.run_if(
+                in_state(GameState::InGame)
+                    .and(resource_exists::<PendingLoad>)
+                    .and(any_with_component::<TrainCar>),
+            ),
+        )
+        .add_systems(
+            Update,
+            (quick_save.run_if(in_state(InGameState::Running)), save_game)
+                .chain()
+                .run_if(in_state(GameState::InGame)),
+        );
+}
+
+pub fn read_save(path: impl AsRef<Path>) -> Result<SaveData, SaveError> {
+    let value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    migrate(value)
+}
+
+fn write_save(path: impl AsRef<Path>, save: &SaveData) -> Result<(), SaveError> {
+    if let Some(dir) = path.as_ref().parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(save)?)?;
+    Ok(())
+}
+
+fn migrate(mut value: Value) -> Result<SaveData, SaveError> {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version == 0 || version > SAVE_VERSION as u64 {
+        return Err(SaveError::UnknownVersion(version));
+    }
+    for migration in &MIGRATIONS[(version - 1) as usize..] {
+        migration(&mut value);
+    }
+    value["version"] = SAVE_VERSION.into();
+    Ok(serde_json::from_value(value)?)
+}
+
+fn quick_save(keys: Res<ButtonInput<KeyCode>>, mut ev: EventWriter<SaveGame>) {
+    if keys.just_pressed(KeyCode::F5) {
+        ev.write(SaveGame);
+    }
+}
+
+fn save_game(
+    mut ev: EventReader<SaveGame>,
+    mut finished: EventWriter<SaveFinished>,
+    train: Single<&Train>,
+    locomotive: Single<(&Inventory, &Boiler, &Health), With<Locomotive>>,
+    train_state: Res<State<TrainState>>,
+    contracts: Res<ActiveContracts>,
+    current_stop: Res<CurrentStop>,
+    next_stop: Res<NextStop>,
+    route: Res<RouteMap>,
+    (market, reputation, recruited): (Res<TownMarket>, Res<Reputation>, Res<Recruited>),
+    last_stop_dist: Res<LastStopDist>,
+    game_world: Res<GameWorld>,
+    crew: Query<(&CrewMember, &LivesIn, Option<&WorksAt>)>,
+    cars: Query<(Entity, &TrainCar, &Transform, Option<&Inventory>, &Health)>,
+    buildings: Query<(
+        &Building,
+        &Transform,
+        &Health,
+        Option<&Inventory>,
+        Option<&ResourceProduction>,
+        Option<&Turret>,
+        Option<&BuildingTier>,
+    )>,
+    children: Query<&Children>,
+) {
+    if ev.read().count() == 0 {
+        return;
+    }
+    let (locomotive, boiler, locomotive_health) = *locomotive;
+
+    let mut cars = cars.iter().collect::<Vec<_>>();
+    cars.sort_by(|(_, _, a, _, _), (_, _, b, _, _)| a.translation.x.total_cmp(&b.translation.x));
+
+    // walk each car's hierarchy so parents are always saved before the buildings on top of them
+    let mut saved_buildings = Vec::new();
+    let mut building_entities = Vec::new();
+    let mut to_visit = cars
+        .iter()
+        .enumerate()
+        .map(|(i, (car, _, _, _, _))| (*car, SavedParent::Car(i)))
+        .collect::<Vec<_>>();
+    while let Some((parent, saved_parent)) = to_visit.pop() {
+        for child in children.get(parent).into_iter().flatten() {
+            let Ok((building, transform, health, inventory, production, turret, tier)) =
+                buildings.get(*child)
+            else {
+                continue;
+            };
+            to_visit.push((*child, SavedParent::Building(saved_buildings.len())));
+            building_entities.push(*child);
+            saved_buildings.push(SavedBuilding {
+                parent: saved_parent,
+                offset: transform.translation.xy().to_array(),
+                building_type: building.0.clone(),
+                tier: tier.map_or(0, |it| it.0),
+                inventory: inventory.map(|it| {
+                    it.items
+                        .iter()
+                        .map(|(item, amount)| (item.clone(), *amount))
+                        .collect()
+                }),
+                production_elapsed: production.map(|it| it.timer.elapsed_secs()),
+                production_running: production.is_some_and(|it| it.running),
+                damage: health.max - health.current,
+                kills: turret.map_or(0, |it| it.kills),
+            });
+        }
+    }
+
+    let index_of = |entity: Entity| building_entities.iter().position(|it| *it == entity);
+    let saved_crew = crew
+        .iter()
+        .filter_map(|(member, LivesIn(home), works_at)| {
+            Some(SavedCrewMember {
+                name: member.name.clone(),
+                morale: member.morale,
+                home: index_of(*home)?,
+                workplace: works_at.and_then(|WorksAt(it)| index_of(*it)),
+            })
+        })
+        .collect();
+
+    let save = SaveData {
+        version: SAVE_VERSION,
+        train: SavedTrain {
+            distance: train.distance,
+            velocity: train.velocity,
+            fuel: boiler.fuel,
+            water: boiler.water,
+            locomotive_damage: locomotive_health.max - locomotive_health.current,
+        },
+        train_state: train_state.get().clone(),
+        locomotive_inventory: locomotive
+            .items
+            .iter()
+            .map(|(item, amount)| (item.clone(), *amount))
+            .collect(),
+        cars: cars
+            .iter()
+            .map(|(_, TrainCar(car_type), _, inventory, health)| SavedCar {
+                car_type: *car_type,
+                inventory: inventory.map(|it| {
+                    it.items
+                        .iter()
+                        .map(|(item, amount)| (item.clone(), *amount))
+                        .collect()
+                }),
+                damage: health.max - health.current,
+            })
+            .collect(),
+        contracts: contracts.0.clone(),
+        current_stop: current_stop.0.clone(),
+        next_stop: SavedNextStop {
+            stop: next_stop.stop.clone(),
+            distance: next_stop.distance,
+            name: next_stop.name.clone(),
+        },
+        route: route.clone(),
+        market: market.0.clone(),
+        reputation: reputation.clone(),
+        last_stop_distance: last_stop_dist.0,
+        seed: game_world.seed,
+        rng: SavedRng {
+            seed: game_world.rng.get_seed(),
+            stream: game_world.rng.get_stream(),
+            word_pos: game_world.rng.get_word_pos(),
+        },
+        difficulty: game_world.difficulty,
+        buildings: saved_buildings,
+        crew: Some(saved_crew),
+        recruited: recruited.0,
+    };
+
+    match write_save(SAVE_PATH, &save) {
+        Ok(()) => {
+            info!("Saved game to {SAVE_PATH}");
+            finished.write(SaveFinished(Ok(())));
+        }
+        Err(err) => {
+            error!("Failed to save game: {err}");
+            finished.write(SaveFinished(Err(err.to_string())));
+        }
+    }
+}
+
+fn restore_resources(
+    pending: Res<PendingLoad>,
+    mut train_stats: ResMut<TrainStats>,
+    mut contracts: ResMut<ActiveContracts>,
+    mut last_stop_dist: ResMut<LastStopDist>,
+    mut commands: Commands,
+) {
+    let save = &pending.0;
+
+    train_stats.cars = save.cars.iter().map(|it| it.car_type).collect();
+    contracts.0 = save.contracts.clone();
+    last_stop_dist.0 = save.last_stop_distance;
+
+    let mut rng = <ChaCha8Rng as rand::SeedableRng>::from_seed(save.rng.seed);
+    rng.set_stream(save.rng.stream);
+    rng.set_word_pos(save.rng.word_pos);
+    // older saves only know the next stop, fill in the rest of the line
+    let mut route = save.route.clone();
+    route.extend(&mut rng, save.difficulty);
+    commands.insert_resource(route);
+    commands.insert_resource(TownMarket(save.market.clone()));
+    commands.insert_resource(save.reputation.clone());
+    commands.insert_resource(GameWorld {
+        seed: save.seed,
+        rng,
+        difficulty: save.difficulty,
+    });
+    commands.insert_resource(WorldSeed(save.seed));
+    commands.insert_resource(Recruited(save.recruited));
+    commands.insert_resource(save.difficulty);
+    commands.insert_resource(CurrentStop(save.current_stop.clone()));
+    commands.insert_resource(NextStop {
+        stop: save.next_stop.stop.clone(),
+        distance: save.next_stop.distance,
+        spawned: false,
+        name: save.next_stop.name.clone(),
+    });
+}
+
+fn restore_entities(
+    pending: Res<PendingLoad>,
+    mut train: Single<&mut Train>,
+    mut locomotive: Single<(&mut Inventory, &mut Boiler, &mut Health), With<Locomotive>>,
+    mut next_train_state: ResMut<NextState<TrainState>>,
+    mut cars: Query<
+        (Entity, &Transform, Option<&mut Inventory>, &mut Health),
+        (With<TrainCar>, Without<Locomotive>),
+    >,
+    image_assets: Res<ImageAssets>,
+    definitions: Buildings,
+    mut commands: Commands,
+) {
+    let save = &pending.0;
+
+    train.distance = save.train.distance;
+    train.velocity = save.train.velocity;
+    let (locomotive, boiler, locomotive_health) = &mut *locomotive;
+    locomotive.items = save.locomotive_inventory.iter().cloned().collect();
+    boiler.fuel = save.train.fuel;
+    boiler.water = save.train.water;
+    locomotive_health.damage(save.train.locomotive_damage);
+    next_train_state.set(save.train_state.clone());
+
+    // the stop we are standing at was spawned in the previous session, bring it back. Goblins
+    // and how far their waves got aren't saved, so a save made mid-ambush restarts the fight
+    // from the first wave
+    if save.train_state == TrainState::Stopped
+        && let Some(NumberedStop(stop @ (Stop::Town | Stop::GoblinAttack { .. }), _)) =
+            &save.current_stop
+    {
+        stop.spawn_stop(&mut commands, save.train.distance, &image_assets);
+    }
+
+    let mut cars = cars.iter_mut().collect::<Vec<_>>();
+    cars.sort_by(|(_, a, _, _), (_, b, _, _)| a.translation.x.total_cmp(&b.translation.x));
+    for ((_, _, inventory, health), saved) in cars.iter_mut().zip(&save.cars) {
+        health.damage(saved.damage);
+        if let (Some(inventory), Some(items)) = (inventory, &saved.inventory) {
+            inventory.items = items.iter().cloned().collect();
+        }
+    }
+
+    // `None` for buildings that no longer have a definition, along with everything on them
+    let mut spawned = Vec::with_capacity(save.buildings.len());
+    for saved in &save.buildings {
+        let parent = match saved.parent {
+            SavedParent::Car(i) => cars.get(i).map(|it| Some(it.0)),
+            SavedParent::Building(i) => spawned.get(i).copied(),
+        };
+        let Some(parent) = parent else {
+            warn!(
+                "Left out a {:?} on a car or building that is not in the save",
+                saved.building_type
+            );
+            spawned.push(None);
+            continue;
+        };
+        let definition = definitions.get(&saved.building_type);
+        let (Some(parent), Some(definition)) = (parent, definition) else {
+            warn!(
+                "Left out a {:?} that is no longer defined",
+                saved.building_type
+            );
+            spawned.push(None);
+            continue;
+        };
+        let offset = Vec2::from_array(saved.offset);
+
+        // queued after the parent is spawned, so this also finds the build
+        // locations of buildings restored earlier in this loop
+        commands.queue(RemoveBuildLocation { parent, offset });
+
+        let building = spawn_building(&mut commands, definition, parent, offset, saved.tier);
+        let tier = definition.tier(saved.tier);
+        let mut health = Health::new(definition.health * tier.health);
+        health.damage(saved.damage);
+        commands.entity(building).insert(health);
+        if let Some(items) = &saved.inventory {
+            commands.entity(building).insert(Inventory {
+                items: items.iter().cloned().collect(),
+            });
+        }
+        if let (Some(elapsed), Some(recipe)) = (saved.production_elapsed, &definition.production) {
+            let mut production = ResourceProduction::new(recipe.clone());
+            production.set_tier(tier);
+            production
+                .timer
+                .set_elapsed(Duration::from_secs_f32(elapsed));
+            production.running = saved.production_running;
+            commands.entity(building).insert(production);
+        }
+        if let Some(stats) = &definition.turret {
+            let mut turret = Turret::new(stats.clone());
+            turret.kills = saved.kills;
+            commands.entity(building).insert(turret);
+        }
+        spawned.push(Some(building));
+    }
+
+    match &save.crew {
+        Some(crew) => {
+            for saved in crew {
+                let Some(home) = spawned.get(saved.home) else {
+                    warn!("Left out {}, whose home is not in the save", saved.name);
+                    continue;
+                };
+                let Some(home) = *home else {
+                    continue;
+                };
+                let mut member = commands.spawn((
+                    StateScoped(GameState::InGame),
+                    CrewMember {
+                        name: saved.name.clone(),
+                        morale: saved.morale,
+                    },
+                    LivesIn(home),
+                ));
+                if let Some(workplace) = saved
+                    .workplace
+                    .and_then(|it| spawned.get(it).copied().flatten())
+                {
+                    member.insert(WorksAt(workplace));
+                }
+            }
+        }
+        None => {
+            for (saved, building) in save.buildings.iter().zip(&spawned) {
+                if let Some(building) = building
+                    && definitions
+                        .get(&saved.building_type)
+                        .is_some_and(|it| it.is_housing())
+                {
+                    commands.queue(MoveIn(*building));
+                }
+            }
+        }
+    }
+
+    commands.remove_resource::<PendingLoad>();
+}
+
+struct RemoveBuildLocation {
+    parent: Entity,
+    offset: Vec2,
+}
+
+impl Command for RemoveBuildLocation {
+    fn apply(self, world: &mut World) {
+        let Some(children) = world.get::<Children>(self.parent) else {
+            return;
+        };
+        let occupied = children
+            .iter()
+            .filter(|child| {
+                world
+                    .get::<BuildLocation>(*child)
+                    .is_some_and(|it| it.0 == self.offset)
+            })
+            .collect::<Vec<_>>();
+        for entity in occupied {
+            world.despawn(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A save as the first version wrote it: one farm on the only car, a contract and a town
+    /// up ahead.
+    fn version_one() -> Value {
+        serde_json::json!({
+            "version": 1,
+            "train": { "distance": 120.0, "velocity": 3.0 },
+            "train_state": "Advancing",
+            "train_stats": { "length": 1, "acceleration": 1.0, "max_velocity": 27.0 },
+            "contracts": [
+                { "required": ["Wood", 5], "reward": ["Money", 10], "stop_number": 2 }
+            ],
+            "current_stop": ["Town", 1],
+            "next_stop": { "stop": ["Town", 2], "distance": 900.0, "name": "Ashford" },
+            "last_stop_distance": 100.0,
+            "rng": { "seed": vec![0; 32], "stream": 0, "word_pos": 0 },
+            "buildings": [
+                {
+                    "parent": { "Car": 0 },
+                    "offset": [0.0, 40.0],
+                    "building_type": "Farm",
+                    "inventory": null,
+                    "production_elapsed": 0.5
+                }
+            ]
+        })
+    }
+
+    #[test]
+    fn first_version_saves_load() {
+        let save = migrate(version_one()).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.cars.len(), 1);
+        assert_eq!(save.buildings.len(), 1);
+        assert_eq!(
+            save.buildings[0].building_type,
+            BuildingType("farm".to_string())
+        );
+        assert_eq!(save.buildings[0].tier, 0);
+        assert_eq!(save.contracts[0].required, (Item::WOOD, 5));
+        assert_eq!(save.route.stops.len(), 2);
+        assert!(save.crew.is_none());
+    }
+
+    #[test]
+    fn old_building_types_have_definitions() {
+        for (old, id) in LEGACY_BUILDING_TYPES {
+            let path = format!("assets/buildings/{id}.building.ron");
+            assert!(Path::new(&path).exists(), "{old} has no {path}");
+        }
+    }
+}
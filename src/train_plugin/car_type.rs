@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ImageAssets,
+    build_plugin::Slot,
+    resources_plugin::{Item, ItemCategory},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum CarType {
+    Standard,
+    Flatbed,
+    Tanker,
+    Armoured,
+    Passenger,
+}
+
+impl CarType {
+    pub fn iterator() -> impl Iterator<Item = Self> {
+        [
+            Self::Standard,
+            Self::Flatbed,
+            Self::Tanker,
+            Self::Armoured,
+            Self::Passenger,
+        ]
+        .into_iter()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CarType::Standard => "Boxcar",
+            CarType::Flatbed => "Flatbed",
+            CarType::Tanker => "Tanker",
+            CarType::Armoured => "Armoured Car",
+            CarType::Passenger => "Passenger Car",
+        }
+    }
+
+    /// Every type shares the boxcar's placeholder art for now, tinted to tell them apart.
+    pub fn sprite(&self, image_assets: &ImageAssets) -> Sprite {
+        Sprite {
+            image: image_assets.train_car.clone(),
+            color: self.tint(),
+            ..Default::default()
+        }
+    }
+
+    fn tint(&self) -> Color {
+        match self {
+            CarType::Standard => Color::WHITE,
+            CarType::Flatbed => Color::srgb(0.85, 0.7, 0.5),
+            CarType::Tanker => Color::srgb(0.6, 0.75, 1.0),
+            CarType::Armoured => Color::srgb(0.6, 0.6, 0.6),
+            CarType::Passenger => Color::srgb(0.7, 1.0, 0.7),
+        }
+    }
+
+    pub fn get_build_locations(&self) -> Vec<(Vec2, Slot)> {
+        match self {
+            CarType::Standard => vec![
+                (Vec2::new(-30.0, 0.0), Slot::Deck),
+                (Vec2::new(30.0, 0.0), Slot::Deck),
+            ],
+            CarType::Flatbed => vec![
+                (Vec2::new(-45.0, 0.0), Slot::Deck),
+                (Vec2::new(0.0, 0.0), Slot::Deck),
+                (Vec2::new(45.0, 0.0), Slot::Deck),
+            ],
+            CarType::Tanker => vec![(Vec2::new(0.0, 40.0), Slot::Mount)],
+            CarType::Armoured => vec![
+                (Vec2::new(-45.0, 40.0), Slot::Mount),
+                (Vec2::new(0.0, 0.0), Slot::Deck),
+                (Vec2::new(45.0, 40.0), Slot::Mount),
+            ],
+            CarType::Passenger => vec![
+                (Vec2::new(-30.0, 0.0), Slot::Cabin),
+                (Vec2::new(30.0, 0.0), Slot::Cabin),
+            ],
+        }
+    }
+
+    /// Weight of the empty car in tonnes.
+    pub fn weight(&self) -> f32 {
+        match self {
+            CarType::Standard => 10.0,
+            CarType::Flatbed => 8.0,
+            CarType::Tanker => 12.0,
+            CarType::Armoured => 20.0,
+            CarType::Passenger => 10.0,
+        }
+    }
+
+    pub fn max_health(&self) -> f32 {
+        match self {
+            CarType::Standard => 40.0,
+            CarType::Flatbed => 30.0,
+            CarType::Tanker => 40.0,
+            CarType::Armoured => 100.0,
+            CarType::Passenger => 35.0,
+        }
+    }
+
+    pub fn get_cost(&self) -> Vec<(Item, usize)> {
+        match self {
+            CarType::Standard => vec![(Item::WOOD, 20), (Item::METAL, 5)],
+            CarType::Flatbed => vec![(Item::WOOD, 25), (Item::METAL, 5)],
+            CarType::Tanker => vec![(Item::WOOD, 10), (Item::METAL, 15)],
+            CarType::Armoured => vec![(Item::BRICK, 10), (Item::METAL, 20)],
+            CarType::Passenger => vec![(Item::WOOD, 30), (Item::GLASS, 5)],
+        }
+    }
+
+    /// The kind of item the car itself holds, for cars that carry cargo without a building.
+    pub fn stores(&self) -> Option<ItemCategory> {
+        match self {
+            CarType::Tanker => Some(ItemCategory::Liquid),
+            _ => None,
+        }
+    }
+
+    /// How much the car's own [`stores`](Self::stores) hold.
+    pub fn capacity(&self) -> usize {
+        match self {
+            CarType::Tanker => 150,
+            _ => 0,
+        }
+    }
+
+    /// Extra beds in every [`BuildingType::Housing`](crate::build_plugin::BuildingType) on this car.
+    pub fn housing_bonus(&self) -> usize {
+        match self {
+            CarType::Passenger => 2,
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+
+use crate::resources_plugin::{Inventory, Item, take_items, total_owned};
+
+/// Distance one unit of Coal keeps the fire going for.
+pub const COAL_ENERGY: f32 = 25.0;
+/// Distance one unit of Wood keeps the fire going for, burnt when the train runs out of Coal.
+pub const WOOD_ENERGY: f32 = 10.0;
+/// Distance one unit of Water lasts in the boiler.
+pub const WATER_ENERGY: f32 = 40.0;
+/// Extra fuel burnt per unit/s² of acceleration, on top of the distance travelled.
+pub const ACCELERATION_FUEL_COST: f32 = 2.0;
+/// How quickly the train slows down once the fire goes out.
+pub const COAST_DECELERATION: f32 = 0.5;
+
+/// What is already loaded into the locomotive, topped up from the train's inventories as it burns.
+#[derive(Component, Default)]
+pub struct Boiler {
+    pub fuel: f32,
+    pub water: f32,
+}
+
+impl Boiler {
+    /// Uses up enough fuel and water to do `work`, shovelling in more from `inventories` as
+    /// needed. Returns false, leaving the boiler topped up with whatever was found, if the
+    /// train ran out of either.
+    pub fn burn(&mut self, work: f32, inventories: &mut Query<&mut Inventory>) -> bool {
+        while self.fuel < work {
+            if take_items(&mut *inventories, &Item::COAL, 1) == 0 {
+                self.fuel += COAL_ENERGY;
+            } else if take_items(&mut *inventories, &Item::WOOD, 1) == 0 {
+                self.fuel += WOOD_ENERGY;
+            } else {
+                return false;
+            }
+        }
+        while self.water < work {
+            if take_items(&mut *inventories, &Item::WATER, 1) == 0 {
+                self.water += WATER_ENERGY;
+            } else {
+                return false;
+            }
+        }
+        self.fuel -= work;
+        self.water -= work;
+        true
+    }
+
+    /// How far the train can go on what is in the boiler plus everything carried in `inventories`.
+    pub fn range<'a>(&self, inventories: impl Iterator<Item = &'a Inventory> + Clone) -> f32 {
+        let coal = total_owned(inventories.clone(), &Item::COAL);
+        let wood = total_owned(inventories.clone(), &Item::WOOD);
+        let water = total_owned(inventories, &Item::WATER);
+        (self.fuel + coal as f32 * COAL_ENERGY + wood as f32 * WOOD_ENERGY)
+            .min(self.water + water as f32 * WATER_ENERGY)
+    }
+}
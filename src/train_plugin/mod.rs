@@ -1,11 +1,26 @@
 use bevy::{math::FloatPow, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use car_type::CarType;
+use fuel::{ACCELERATION_FUEL_COST, Boiler, COAST_DECELERATION};
 
 use crate::{
-    GameState, ImageAssets,
-    build_plugin::{BuildLocation, Building},
+    GameState, ImageAssets, InGameState,
+    build_plugin::{
+        BuildLocation, Building, DEMOLITION_REFUND, definitions::Buildings, format_cost,
+        inspect_on_click, tiers::BuildingTier,
+    },
+    combat_plugin::{Defeat, Health, LOCOMOTIVE_HEALTH},
+    crew_plugin::Rehouse,
+    resources_plugin::{
+        AcceptedItems, Capacity, Holds, Inventory, Item, ItemRegistry, Items, can_afford,
+        store_what_fits, take_items,
+    },
     world_plugin::{CurrentStop, GenerateNextStop, NextStop, Stop},
 };
 
+pub mod car_type;
+pub mod fuel;
 mod train_speed_ui;
 
 #[derive(Resource, Default)]
@@ -13,20 +28,67 @@ pub struct MaxPixelHeightOfTrain {
     pub height: f32,
 }
 
+/// Weight of the locomotive in tonnes.
+pub const LOCOMOTIVE_WEIGHT: f32 = 30.0;
+pub const CABOOSE_WEIGHT: f32 = 5.0;
+/// Pulling force of the locomotive, a train this heavy in tonnes accelerates at 1 unit/s².
+pub const LOCOMOTIVE_POWER: f32 = 75.0;
+/// Top speed of a train that weighs exactly [`LOCOMOTIVE_POWER`].
+pub const BASE_MAX_VELOCITY: f32 = 27.0;
+pub const MAX_VELOCITY_CAP: f32 = 35.0;
+pub const BRAKE_FORCE: f32 = 225.0;
+
 #[derive(Resource)]
 pub struct TrainStats {
-    pub length: usize,
+    /// The cars between the locomotive and the caboose, front to back.
+    pub cars: Vec<CarType>,
+    /// Everything on the rails in tonnes, recalculated every tick by [`update_train_mass`].
+    pub mass: f32,
     pub acceleration: f32,
     pub max_velocity: f32,
+    /// How quickly the brakes slow the train down.
+    pub deceleration: f32,
+}
+
+impl Default for TrainStats {
+    fn default() -> Self {
+        let mut stats = Self {
+            cars: vec![CarType::Standard, CarType::Standard],
+            mass: 0.0,
+            acceleration: 0.0,
+            max_velocity: 0.0,
+            deceleration: 0.0,
+        };
+        stats.set_mass(LOCOMOTIVE_POWER);
+        stats
+    }
 }
 
 impl TrainStats {
+    pub fn length(&self) -> usize {
+        self.cars.len()
+    }
+
+    /// Derives how the train handles from how much the locomotive has to pull.
+    pub fn set_mass(&mut self, mass: f32) {
+        self.mass = mass;
+        self.acceleration = LOCOMOTIVE_POWER / mass;
+        self.max_velocity =
+            (BASE_MAX_VELOCITY * (LOCOMOTIVE_POWER / mass).sqrt()).min(MAX_VELOCITY_CAP);
+        self.deceleration = BRAKE_FORCE / mass;
+    }
+
+    /// How far the train travels before coming to a halt when braking at `velocity`.
+    pub fn braking_distance(&self, velocity: f32) -> f32 {
+        velocity.squared() / (2.0 * self.deceleration)
+    }
+
     pub fn train_size(&self) -> f32 {
-        (self.length as f32) * CAR_SIZE
+        (self.length() as f32) * CAR_SIZE
     }
 }
 
-#[derive(States, Debug, Hash, PartialEq, Eq, Clone, Default)]
+#[derive(States, Debug, Hash, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
 pub enum TrainState {
     #[default]
     Stopped,
@@ -35,35 +97,58 @@ pub enum TrainState {
 }
 
 pub fn train_plugin(app: &mut App) {
-    app.insert_resource(TrainStats {
-        length: 2,
-        acceleration: 1.0,
-        max_velocity: 27.0,
-    })
-    .add_event::<AdvanceEvent>()
-    .add_event::<StopEvent>()
-    .init_state::<TrainState>()
-    .add_systems(OnEnter(GameState::InGame), spawn_train)
-    .init_resource::<MaxPixelHeightOfTrain>()
-    .add_systems(OnEnter(GameState::InGame), train_speed_ui::make_ui)
-    .add_systems(
-        FixedPostUpdate,
-        train_speed_ui::update_train_speed.run_if(in_state(GameState::InGame)),
-    )
-    .add_systems(
-        FixedUpdate,
-        (
-            start_advancing.run_if(in_state(TrainState::Stopped).and(in_state(GameState::InGame))),
-            move_train.run_if(
-                (in_state(TrainState::Advancing).or(in_state(TrainState::Arriving)))
-                    .and(in_state(GameState::InGame)),
+    app.init_resource::<TrainStats>()
+        .add_event::<AdvanceEvent>()
+        .add_event::<AddCarEvent>()
+        .add_event::<RemoveCarEvent>()
+        .add_event::<StopEvent>()
+        .init_state::<TrainState>()
+        .add_systems(OnEnter(GameState::InGame), spawn_train)
+        .init_resource::<MaxPixelHeightOfTrain>()
+        .add_systems(OnEnter(GameState::InGame), train_speed_ui::make_ui)
+        .add_systems(
+            FixedPostUpdate,
+            (
+                train_speed_ui::update_train_speed,
+                train_speed_ui::update_fuel_gauge,
+                train_speed_ui::update_train_weight.run_if(resource_changed::<TrainStats>),
+            )
+                .run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                start_advancing.run_if(in_state(TrainState::Stopped)),
+                move_train
+                    .run_if(in_state(TrainState::Advancing).or(in_state(TrainState::Arriving)))
+                    .after(update_train_mass),
+            )
+                .run_if(in_state(GameState::InGame).and(in_state(InGameState::Running))),
+        )
+        .add_systems(
+            FixedUpdate,
+            update_train_mass.run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(
+            FixedUpdate,
+            update_train_height.run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(
+            Update,
+            (add_car, remove_car).run_if(
+                in_state(GameState::InGame)
+                    .and(in_state(InGameState::Running))
+                    .and(in_state(TrainState::Stopped)),
             ),
-        ),
-    )
-    .add_systems(
-        FixedUpdate,
-        update_train_height.run_if(in_state(GameState::InGame)),
-    );
+        )
+        .add_systems(
+            OnExit(GameState::InGame),
+            |mut commands: Commands, mut train_state: ResMut<NextState<TrainState>>| {
+                commands.insert_resource(TrainStats::default());
+                commands.insert_resource(MaxPixelHeightOfTrain::default());
+                train_state.set(TrainState::Stopped);
+            },
+        );
 }
 
 pub const CAR_SIZE: f32 = 144.0;
@@ -73,7 +158,7 @@ pub struct Locomotive;
 #[derive(Component)]
 pub struct Caboose;
 #[derive(Component)]
-pub struct TrainCar;
+pub struct TrainCar(pub CarType);
 #[derive(Component)]
 pub struct Train {
     pub distance: f32,
@@ -83,19 +168,47 @@ pub struct Train {
 #[derive(Event)]
 pub struct AdvanceEvent;
 
+#[derive(Event)]
+pub struct AddCarEvent(pub CarType);
+
+#[derive(Event)]
+pub struct RemoveCarEvent;
+
 #[derive(Event)]
 pub struct StopEvent {
     stop: Stop,
     name: String,
 }
 
+/// How much cargo the locomotive has room for besides its boiler.
+pub const LOCOMOTIVE_CAPACITY: usize = 150;
+
+/// What the locomotive carries at the start of a run, enough for the first few buildings.
+pub fn starting_cargo() -> Inventory {
+    Inventory {
+        items: [
+            (Item::WOOD, 40),
+            (Item::CLAY, 10),
+            (Item::BRICK, 10),
+            (Item::METAL, 6),
+            (Item::COAL, 12),
+            (Item::WATER, 20),
+            (Item::FOOD, 20),
+        ]
+        .into_iter()
+        .collect(),
+    }
+}
+
 fn spawn_train(
     mut commands: Commands,
     image_assets: Res<ImageAssets>,
     train_stats: Res<TrainStats>,
+    items: Items,
 ) {
     commands
         .spawn((
+            StateScoped(GameState::InGame),
             Visibility::default(),
             Transform::default(),
             Train {
@@ -104,45 +217,194 @@ fn spawn_train(
             },
         ))
         .with_children(|parent| {
-            parent.spawn((
-                Sprite::from_image(image_assets.train_locomotive.clone()),
-                Name::new("Locomotive"),
-                Locomotive,
-            ));
-            for i in 0..train_stats.length {
-                parent.spawn((
-                    Sprite::from_image(image_assets.train_car.clone()),
-                    Name::new(format!("Car{i}")),
-                    TrainCar,
-                    Transform::from_xyz(CAR_SIZE * (i as f32 + 1.), 0., 0.),
-                    children![
-                        (BuildLocation(Vec2::new(-30.0, 0.0)), Transform::default()),
-                        (BuildLocation(Vec2::new(30.0, 0.0)), Transform::default())
-                    ],
-                ));
+            parent
+                .spawn((
+                    Sprite::from_image(image_assets.train_locomotive.clone()),
+                    Name::new("Locomotive"),
+                    Locomotive,
+                    starting_cargo(),
+                    Capacity(LOCOMOTIVE_CAPACITY),
+                    Boiler::default(),
+                    Health::new(LOCOMOTIVE_HEALTH),
+                    Pickable::default(),
+                ))
+                .observe(inspect_on_click);
+            for (i, car_type) in train_stats.cars.iter().enumerate() {
+                spawn_train_car(parent, &image_assets, &items, i, *car_type);
             }
             parent.spawn((
                 Sprite::from_image(image_assets.train_caboose.clone()),
                 Name::new("Caboose"),
                 Caboose,
-                Transform::from_xyz(CAR_SIZE * (train_stats.length as f32 + 1.), 0., 0.),
+                Transform::from_xyz(CAR_SIZE * (train_stats.length() as f32 + 1.), 0., 0.),
             ));
         });
 }
 
+fn spawn_train_car(
+    parent: &mut ChildSpawnerCommands,
+    image_assets: &ImageAssets,
+    items: &ItemRegistry,
+    i: usize,
+    car_type: CarType,
+) {
+    let mut car = parent.spawn((
+        car_type.sprite(image_assets),
+        Name::new(format!("Car{i}")),
+        TrainCar(car_type),
+        Health::new(car_type.max_health()),
+        Transform::from_xyz(CAR_SIZE * (i as f32 + 1.), 0., 0.),
+    ));
+    if let Some(category) = car_type.stores() {
+        car.insert((
+            Inventory::default(),
+            AcceptedItems(items.of_category(category)),
+            Capacity(car_type.capacity()),
+            Pickable::default(),
+        ))
+        .observe(inspect_on_click);
+    }
+    car.with_children(|parent| {
+        for (build_location, slot) in car_type.get_build_locations() {
+            parent.spawn((BuildLocation(build_location, slot), Transform::default()));
+        }
+    });
+}
+
+fn add_car(
+    mut ev: EventReader<AddCarEvent>,
+    mut train_stats: ResMut<TrainStats>,
+    train: Single<Entity, With<Train>>,
+    mut caboose: Single<&mut Transform, With<Caboose>>,
+    mut inventories: Query<&mut Inventory>,
+    image_assets: Res<ImageAssets>,
+    items: Items,
+    mut commands: Commands,
+) {
+    for AddCarEvent(car_type) in ev.read() {
+        let cost = car_type.get_cost();
+        if !can_afford(inventories.as_readonly(), &cost) {
+            info!("Can't afford a new {}", car_type.name());
+            continue;
+        }
+        for (item, amount) in &cost {
+            take_items(&mut inventories, item, *amount);
+        }
+
+        let i = train_stats.length();
+        commands.entity(*train).with_children(|parent| {
+            spawn_train_car(parent, &image_assets, &items, i, *car_type);
+        });
+        train_stats.cars.push(*car_type);
+        caboose.translation.x = CAR_SIZE * (train_stats.length() as f32 + 1.);
+    }
+}
+
+/// Scraps the last car, storing [`DEMOLITION_REFUND`] of its cost, what demolishing each
+/// building on it would and everything they held on the rest of the train. Anything that
+/// doesn't fit is lost, and crew living on the car move to other housing, see [`Rehouse`].
+fn remove_car(
+    mut ev: EventReader<RemoveCarEvent>,
+    mut train_stats: ResMut<TrainStats>,
+    cars: Query<(Entity, &TrainCar, &Transform), (Without<Caboose>, Without<Locomotive>)>,
+    mut caboose: Single<&mut Transform, With<Caboose>>,
+    children: Query<&Children>,
+    buildings: Query<(Entity, &Building, &BuildingTier)>,
+    mut holds: Holds,
+    definitions: Buildings,
+    items: Items,
+    mut commands: Commands,
+) {
+    for _ in ev.read() {
+        if train_stats.length() <= 1 {
+            info!("Can't scrap the last car");
+            continue;
+        }
+        let Some((car, TrainCar(car_type), _)) = cars
+            .iter()
+            .max_by(|(_, _, a), (_, _, b)| a.translation.x.total_cmp(&b.translation.x))
+        else {
+            continue;
+        };
+
+        let mut refund = car_type
+            .get_cost()
+            .into_iter()
+            .map(|(item, amount)| (item, (amount as f32 * DEMOLITION_REFUND) as usize))
+            .collect::<Vec<_>>();
+        for (_, building, BuildingTier(level)) in
+            buildings.iter_many(children.iter_descendants(car))
+        {
+            if let Some(definition) = definitions.get(&building.0) {
+                refund.extend(definition.refund(*level));
+            }
+        }
+        let scrapped = std::iter::once(car)
+            .chain(
+                buildings
+                    .iter_many(children.iter_descendants(car))
+                    .map(|(entity, ..)| entity),
+            )
+            .collect::<Vec<_>>();
+        for entity in &scrapped {
+            if let Ok((_, inventory, ..)) = holds.get(*entity) {
+                refund.extend(
+                    inventory
+                        .items
+                        .iter()
+                        .map(|(item, amount)| (item.clone(), *amount)),
+                );
+            }
+        }
+        let lost = store_what_fits(&mut holds, &refund, &scrapped);
+        if !lost.is_empty() {
+            warn!(
+                "No room on the train for {}, it was left behind",
+                format_cost(&lost, &items)
+            );
+        }
+
+        commands.queue(Rehouse(scrapped));
+        commands.entity(car).despawn();
+        train_stats.cars.pop();
+        caboose.translation.x = CAR_SIZE * (train_stats.length() as f32 + 1.);
+    }
+}
+
+fn update_train_mass(
+    mut train_stats: ResMut<TrainStats>,
+    buildings: Query<&Building>,
+    inventories: Query<&Inventory>,
+    definitions: Buildings,
+    items: Items,
+) {
+    let mass = LOCOMOTIVE_WEIGHT
+        + CABOOSE_WEIGHT
+        + train_stats.cars.iter().map(CarType::weight).sum::<f32>()
+        + buildings
+            .iter()
+            .filter_map(|it| definitions.get(&it.0))
+            .map(|it| it.weight)
+            .sum::<f32>()
+        + inventories.iter().map(|it| it.weight(&items)).sum::<f32>();
+    if mass != train_stats.mass {
+        train_stats.set_mass(mass);
+    }
+}
+
 fn start_advancing(
     mut ev: EventReader<AdvanceEvent>,
     mut next_state: ResMut<NextState<TrainState>>,
-    mut current_stop: ResMut<CurrentStop>,
 ) {
     for _ in ev.read() {
         info!("Starting to advance!");
         next_state.set(TrainState::Advancing);
-
-        current_stop.0 = None;
     }
 }
 
+/// The train only leaves its stop once the boiler gets it moving, until then it stays put so
+/// the player can still buy fuel. Running out of fuel coasts the train to a halt, and a train
+/// stranded between stops ends the run since nothing can bring it more.
 fn move_train(
     mut train: Query<&mut Train>,
     train_stats: Res<TrainStats>,
@@ -152,25 +414,57 @@ fn move_train(
     mut commands: Commands,
     time: Res<Time>,
     mut ev: EventWriter<StopEvent>,
+    mut boiler: Single<&mut Boiler, With<Locomotive>>,
+    mut inventories: Query<&mut Inventory>,
+    mut in_game_state: ResMut<NextState<InGameState>>,
+    mut defeat: ResMut<Defeat>,
 ) {
     let mut train = train.single_mut().unwrap();
 
-    train.velocity = if next_stop.distance - train.distance < (train.velocity * 3.1) {
-        (((next_stop.distance - train.distance) * 0.8) + 0.1).min(train.velocity)
+    let remaining = next_stop.distance - train.distance;
+    if remaining <= train_stats.braking_distance(train.velocity) {
+        // follow the braking curve down so the train comes to rest right at the stop
+        train.velocity = (2.0 * train_stats.deceleration * remaining.max(0.0)).sqrt();
     } else {
-        train.velocity + train_stats.acceleration * time.delta_secs()
-    };
+        let mut velocity = train.velocity + train_stats.acceleration * time.delta_secs();
 
-    train.velocity = train.velocity.min(train_stats.max_velocity);
+        // a heavier load can lower the top speed while already going faster, ease off instead
+        if velocity > train_stats.max_velocity {
+            velocity = (train.velocity - train_stats.deceleration * time.delta_secs())
+                .max(train_stats.max_velocity);
+        }
+
+        let work = velocity * time.delta_secs()
+            + (velocity - train.velocity).max(0.0) * ACCELERATION_FUEL_COST;
+        train.velocity = if boiler.burn(work, &mut inventories) {
+            if current_stop.0.is_some() {
+                current_stop.0 = None;
+            }
+            velocity
+        } else if current_stop.0.is_some() {
+            warn!("The train could not get going, waiting at the stop");
+            next_state.set(TrainState::Stopped);
+            0.0
+        } else {
+            let velocity = (train.velocity - COAST_DECELERATION * time.delta_secs()).max(0.0);
+            if velocity == 0.0 {
+                info!("The train ran out of fuel between stops");
+                *defeat = Defeat::Stranded;
+                in_game_state.set(InGameState::Defeated);
+            }
+            velocity
+        };
+    }
 
-    train.distance += train.velocity * time.delta_secs();
+    train.distance = (train.distance + train.velocity * time.delta_secs()).min(next_stop.distance);
     // info!("Distance: {}", train.distance);
 
-    if next_stop.distance - train.distance < 17.0 {
+    let remaining = next_stop.distance - train.distance;
+    if remaining <= train_stats.braking_distance(train.velocity) {
         next_state.set(TrainState::Arriving);
     }
 
-    if next_stop.distance - train.distance < 0.1 {
+    if remaining <= 0.0 {
         info!(
             "{} - {} = {}",
             next_stop.distance,
@@ -181,8 +475,6 @@ fn move_train(
         next_state.set(TrainState::Stopped);
         train.velocity = 0.0;
 
-        train.velocity = 0.0;
-
         ev.write(StopEvent {
             stop: next_stop.stop.0.clone(),
             name: next_stop.name.clone(),
@@ -194,31 +486,25 @@ fn move_train(
     }
 }
 
+/// Worked out from scratch whenever a building moves or goes away, since either can lower the
+/// train as well as raise it.
 fn update_train_height(
     mut height: ResMut<MaxPixelHeightOfTrain>,
-    changed_comp: Query<&GlobalTransform, (Changed<GlobalTransform>, With<Building>)>,
-    removed_comp: RemovedComponents<Building>,
+    changed_comp: Query<(), (Changed<GlobalTransform>, With<Building>)>,
+    mut removed_comp: RemovedComponents<Building>,
     buildings: Query<&GlobalTransform, With<Building>>,
 ) {
-    for changed in changed_comp.iter() {
-        let t = changed.translation();
-
-        if t.y > height.height {
-            height.height = t.y;
-        }
+    if changed_comp.is_empty() && removed_comp.read().count() == 0 {
+        return;
     }
+    let nh = buildings
+        .iter()
+        .map(|it| it.translation().y)
+        .fold(0.0f32, f32::max);
 
-    if !removed_comp.is_empty() {
-        let mut nh = 0.0f32;
-
-        for i in buildings {
-            nh = nh.max(i.translation().y)
-        }
-
-        // not updating if not changed because performance stuff
-        // (in case someone does change detection on the height)
-        if nh != height.height {
-            height.height = nh;
-        }
+    // not updating if not changed because performance stuff
+    // (in case someone does change detection on the height)
+    if nh != height.height {
+        height.height = nh;
     }
 }
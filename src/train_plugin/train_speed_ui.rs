@@ -1,6 +1,12 @@
 use bevy::prelude::*;
 
-use super::Train;
+use crate::{
+    GameState,
+    resources_plugin::{Inventory, Item, total_owned},
+    world_plugin::NextStop,
+};
+
+use super::{Locomotive, Train, TrainStats, fuel::Boiler};
 
 #[derive(Component)]
 pub struct SpeedUI;
@@ -8,9 +14,12 @@ pub struct SpeedUI;
 const SPEED_FONT_SIZE: f32 = 20.0;
 const SPEED_FONT_COLOR: Color = Color::srgb(1.0, 1.0, 1.0);
 const SPEED_TEXT_PADDING: Val = Val::Px(10.0);
+const OUT_OF_FUEL_COLOR: Color = Color::srgb(1.0, 0.3, 0.3);
+const LOW_FUEL_COLOR: Color = Color::srgb(1.0, 0.7, 0.2);
 
 pub(crate) fn make_ui(mut commands: Commands) {
     commands.spawn((
+        StateScoped(GameState::InGame),
         Text::new("Speed: "),
         TextFont {
             font_size: SPEED_FONT_SIZE,
@@ -24,14 +33,48 @@ pub(crate) fn make_ui(mut commands: Commands) {
             left: SPEED_TEXT_PADDING,
             ..default()
         },
-        children![(
-            TextSpan::default(),
-            TextFont {
-                font_size: SPEED_FONT_SIZE,
-                ..default()
-            },
-            TextColor(SPEED_FONT_COLOR),
-        )],
+        children![
+            (
+                TextSpan::default(),
+                TextFont {
+                    font_size: SPEED_FONT_SIZE,
+                    ..default()
+                },
+                TextColor(SPEED_FONT_COLOR),
+            ),
+            (
+                TextSpan::new("  Fuel: "),
+                TextFont {
+                    font_size: SPEED_FONT_SIZE,
+                    ..default()
+                },
+                TextColor(SPEED_FONT_COLOR),
+            ),
+            (
+                TextSpan::default(),
+                TextFont {
+                    font_size: SPEED_FONT_SIZE,
+                    ..default()
+                },
+                TextColor(SPEED_FONT_COLOR),
+            ),
+            (
+                TextSpan::new("  Weight: "),
+                TextFont {
+                    font_size: SPEED_FONT_SIZE,
+                    ..default()
+                },
+                TextColor(SPEED_FONT_COLOR),
+            ),
+            (
+                TextSpan::default(),
+                TextFont {
+                    font_size: SPEED_FONT_SIZE,
+                    ..default()
+                },
+                TextColor(SPEED_FONT_COLOR),
+            )
+        ],
     ));
 }
 
@@ -49,3 +92,37 @@ pub(crate) fn update_train_speed(
 
     *writer.text(*speed_ui, 1) = format!("{:.2}", speed);
 }
+
+pub(crate) fn update_train_weight(
+    train_stats: Res<TrainStats>,
+    speed_ui: Single<Entity, (With<SpeedUI>, With<Text>)>,
+    mut writer: TextUiWriter,
+) {
+    *writer.text(*speed_ui, 5) = format!("{:.0}t", train_stats.mass);
+}
+
+/// Turns orange once the train carries too little to reach the next stop, not counting the
+/// extra burnt speeding up.
+pub(crate) fn update_fuel_gauge(
+    inventories: Query<&Inventory>,
+    boiler: Single<&Boiler, With<Locomotive>>,
+    train: Single<&Train>,
+    next_stop: Res<NextStop>,
+    speed_ui: Single<Entity, (With<SpeedUI>, With<Text>)>,
+    mut writer: TextUiWriter,
+) {
+    let coal = total_owned(inventories.iter(), &Item::COAL);
+    let wood = total_owned(inventories.iter(), &Item::WOOD);
+    let water = total_owned(inventories.iter(), &Item::WATER);
+    let range = boiler.range(inventories.iter());
+
+    let (warning, color) = if coal + wood == 0 || water == 0 {
+        (" - out of fuel", OUT_OF_FUEL_COLOR)
+    } else if range < next_stop.distance - train.distance {
+        (" - not enough to reach the next stop", LOW_FUEL_COLOR)
+    } else {
+        ("", SPEED_FONT_COLOR)
+    };
+    *writer.text(*speed_ui, 3) = format!("{coal} Coal, {wood} Wood, {water} Water{warning}");
+    *writer.color(*speed_ui, 3) = TextColor(color);
+}
@@ -7,4 +7,6 @@ pub enum InMenu {
     StopMenu,
     BuildMenu,
     BuildingMenu,
+    RouteMap,
+    Journal,
 }
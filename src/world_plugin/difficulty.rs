@@ -0,0 +1,80 @@
+use std::ops::RangeInclusive;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The preset picked on the main menu, the run itself keeps its copy in
+/// [`GameWorld`](super::GameWorld).
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+/// Past this level the last wave of an ambush is led by a boss.
+const BOSS_LEVEL: f32 = 3.5;
+
+impl Difficulty {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    /// How hard things are at a stop, starting around 1 and growing with every stop and every
+    /// kilometre travelled. Everything else here is worked out from it.
+    pub fn level(&self, stop_number: usize, distance: f32) -> f32 {
+        let (start, growth) = match self {
+            Difficulty::Easy => (0.8, 0.6),
+            Difficulty::Normal => (1.0, 1.0),
+            Difficulty::Hard => (1.2, 1.5),
+        };
+        start + (stop_number as f32 * 0.1 + distance / 1000.0) * growth
+    }
+
+    pub fn wave_count(level: f32) -> RangeInclusive<usize> {
+        1..=((1.0 + level * 2.0) as usize).min(12)
+    }
+
+    pub fn wave_size(level: f32) -> RangeInclusive<usize> {
+        1..=((2.0 + level * 3.0) as usize).min(20)
+    }
+
+    pub fn has_boss(level: f32) -> bool {
+        level > BOSS_LEVEL
+    }
+
+    /// How much of an item a contract asks for.
+    pub fn contract_amount(level: f32) -> RangeInclusive<usize> {
+        let scale = level.sqrt();
+        (10.0 * scale) as usize..=(60.0 * scale) as usize
+    }
+
+    /// Multiplies what a contract pays out, bigger late-game contracts pay a little extra.
+    pub fn reward_factor(&self, level: f32) -> f32 {
+        let preset = match self {
+            Difficulty::Easy => 1.25,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 0.8,
+        };
+        preset * (1.0 + (level - 1.0).max(0.0) * 0.1)
+    }
+
+    /// How many stops ahead a contract's deadline falls, which tightens as the run goes on.
+    pub fn deadline(&self, level: f32) -> RangeInclusive<usize> {
+        let slack = if *self == Difficulty::Easy { 1 } else { 0 };
+        2..=(7.0 - level).round().clamp(3.0, 6.0) as usize + slack
+    }
+}
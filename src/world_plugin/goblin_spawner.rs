@@ -1,13 +1,85 @@
 use crate::{
+    GameState,
+    combat_plugin::Health,
     control_panel_plugin::AdvanceBlocker,
-    goblins::Goblin,
+    goblins::{Archer, Boss, Brute, Climber, Goblin, GoblinStats, Saboteur, hit_goblin},
     train_plugin::{MaxPixelHeightOfTrain, TrainStats},
 };
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum GoblinType {
     Basic,
+    Archer,
+    Climber,
+    Saboteur,
+    Boss,
+}
+
+impl GoblinType {
+    pub fn max_health(&self) -> f32 {
+        match self {
+            GoblinType::Basic => 3.0,
+            GoblinType::Archer => 2.0,
+            GoblinType::Climber => 3.0,
+            GoblinType::Saboteur => 2.0,
+            GoblinType::Boss => 30.0,
+        }
+    }
+
+    pub fn stats(&self) -> GoblinStats {
+        match self {
+            GoblinType::Basic => GoblinStats::new(120.0, 2.0, 40.0, 1.0),
+            GoblinType::Archer => GoblinStats::new(100.0, 1.0, 300.0, 1.5),
+            GoblinType::Climber => GoblinStats::new(160.0, 3.0, 40.0, 1.0),
+            GoblinType::Saboteur => GoblinStats::new(180.0, 0.0, 40.0, 1.0),
+            GoblinType::Boss => GoblinStats::new(60.0, 8.0, 60.0, 2.0),
+        }
+    }
+
+    fn size(&self) -> f32 {
+        match self {
+            GoblinType::Boss => 25.0,
+            GoblinType::Basic | GoblinType::Archer => 10.0,
+            GoblinType::Climber | GoblinType::Saboteur => 8.0,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            GoblinType::Basic => Color::srgb(0.0, 1.0, 1.0),
+            GoblinType::Archer => Color::srgb(0.2, 0.8, 0.2),
+            GoblinType::Climber => Color::srgb(1.0, 0.6, 0.0),
+            GoblinType::Saboteur => Color::srgb(0.6, 0.2, 0.8),
+            GoblinType::Boss => Color::srgb(0.9, 0.1, 0.1),
+        }
+    }
+
+    /// How likely this goblin is to turn up in a wave at the given
+    /// [difficulty level](crate::world_plugin::difficulty::Difficulty::level).
+    pub fn weight_at(&self, level: f32) -> f32 {
+        let ramp = |start: f32, max: f32| ((level - start) * 4.0).clamp(0.0, max);
+        match self {
+            GoblinType::Basic => 10.0,
+            GoblinType::Archer => ramp(1.3, 6.0),
+            GoblinType::Saboteur => ramp(1.6, 4.0),
+            GoblinType::Climber => ramp(1.9, 5.0),
+            // bosses lead the last wave instead of being mixed in
+            GoblinType::Boss => 0.0,
+        }
+    }
+
+    pub fn iterator() -> impl Iterator<Item = Self> {
+        [
+            Self::Basic,
+            Self::Archer,
+            Self::Climber,
+            Self::Saboteur,
+            Self::Boss,
+        ]
+        .into_iter()
+    }
 }
 
 #[derive(Component)]
@@ -53,24 +125,42 @@ pub fn spawn_goblins(
             let spread = train_stats.train_size() + SPREAD * 2.0;
 
             for (i, g) in s.0.waves[s.0.current_wave].iter().enumerate() {
+                let mut goblin = commands.spawn((
+                    StateScoped(GameState::InGame),
+                    Goblin,
+                    Health::new(g.max_health()),
+                    g.stats(),
+                    Pickable::default(),
+                    Sprite::from_color(g.color(), Vec2::ONE),
+                    Transform {
+                        translation: Vec3 {
+                            x: ((i as f32 + 0.5) / t as f32) * spread - SPREAD,
+                            y: train_height.height + HEIGHT_ABOVE_TRAIN,
+                            z: 0.0,
+                        },
+                        scale: Vec2::splat(g.size()).extend(1.0),
+                        ..default()
+                    },
+                    AdvanceBlocker,
+                ));
                 match g {
                     GoblinType::Basic => {
-                        commands.spawn((
-                            Goblin,
-                            Sprite::from_color(Color::srgb(0.0, 1.0, 1.0), Vec2::ONE),
-                            Transform {
-                                translation: Vec3 {
-                                    x: ((i as f32 + 0.5) / t as f32) * spread - SPREAD,
-                                    y: train_height.height + HEIGHT_ABOVE_TRAIN,
-                                    z: 0.0,
-                                },
-                                scale: Vec2::new(10.0, 10.0).extend(1.0),
-                                ..default()
-                            },
-                            AdvanceBlocker,
-                        ));
+                        goblin.insert(Brute);
+                    }
+                    GoblinType::Archer => {
+                        goblin.insert(Archer);
+                    }
+                    GoblinType::Climber => {
+                        goblin.insert(Climber);
+                    }
+                    GoblinType::Saboteur => {
+                        goblin.insert(Saboteur::default());
+                    }
+                    GoblinType::Boss => {
+                        goblin.insert(Boss);
                     }
                 }
+                goblin.observe(hit_goblin);
             }
 
             s.0.current_wave += 1;
@@ -0,0 +1,301 @@
+use bevy::prelude::*;
+
+use crate::{
+    GameState, InGameState,
+    resources_plugin::{Inventory, Items, spawn_items, total_owned},
+    ui_state::InMenu,
+};
+
+use super::{
+    reputation::Reputation,
+    route_map::RouteMap,
+    stop_plugin::{ActiveContracts, Contract},
+};
+
+const AT_RISK_COLOR: Color = Color::srgb(0.55, 0.1, 0.1);
+const ON_TRACK_COLOR: Color = Color::srgb(0.15, 0.15, 0.2);
+
+#[derive(Component)]
+struct Journal;
+
+#[derive(Component)]
+struct CloseJournal;
+
+#[derive(Component)]
+struct AbandonButton(usize);
+
+/// The row of the `n`th contract, coloured by whether it is at risk.
+#[derive(Component)]
+struct ContractRow(usize);
+
+/// Where the `n`th contract stands, kept up to date as goods come and go.
+#[derive(Component)]
+struct ContractProgress(usize);
+
+pub fn journal_plugin(app: &mut App) {
+    app.add_systems(OnEnter(InMenu::Journal), spawn_journal)
+        .add_systems(
+            Update,
+            (
+                abandon_buttons,
+                close_journal,
+                redraw_journal,
+                update_progress.run_if(any_inventory_changed),
+            )
+                .chain()
+                .run_if(
+                    in_state(GameState::InGame)
+                        .and(in_state(InGameState::Running))
+                        .and(in_state(InMenu::Journal)),
+                ),
+        );
+}
+
+/// A contract is at risk when its town is no longer on the line ahead, or its deadline is
+/// up at the next stop and the train doesn't carry enough yet.
+fn at_risk(contract: &Contract, route: &RouteMap, owned: usize, stops_left: usize) -> bool {
+    let unreachable = contract
+        .destination
+        .as_ref()
+        .is_some_and(|it| route.get(it.id).is_none());
+    unreachable || (stops_left <= 1 && owned < contract.required.1)
+}
+
+/// The line telling where `contract` stands, and the colour of its row.
+fn contract_status(
+    contract: &Contract,
+    route: &RouteMap,
+    inventories: &Query<&Inventory>,
+) -> (String, Color) {
+    let stop_number = route.get(route.current).unwrap().stop.1;
+    let owned = contract.handed_over + total_owned(inventories.iter(), &contract.required.0);
+    let stops_left = contract.stop_number.saturating_sub(stop_number);
+    let destination = match &contract.destination {
+        Some(destination) if route.get(destination.id).is_none() => {
+            format!("{} (off the line)", destination.name)
+        }
+        Some(destination) => destination.name.clone(),
+        None => "any town".to_string(),
+    };
+    let handed_over = if contract.handed_over > 0 {
+        format!(", {} handed over", contract.handed_over)
+    } else {
+        String::new()
+    };
+    let text = format!(
+        "To {destination}, stop {} ({stops_left} stops left)\nCarrying {owned}/{}{handed_over}",
+        contract.stop_number, contract.required.1,
+    );
+    let color = if at_risk(contract, route, owned, stops_left) {
+        AT_RISK_COLOR
+    } else {
+        ON_TRACK_COLOR
+    };
+    (text, color)
+}
+
+fn spawn_journal(
+    mut commands: Commands,
+    contracts: Res<ActiveContracts>,
+    route: Res<RouteMap>,
+    inventories: Query<&Inventory>,
+    items: Items,
+) {
+    commands
+        .spawn((
+            StateScoped(InMenu::Journal),
+            Journal,
+            Node {
+                width: Val::Vw(40.0),
+                max_height: Val::Vh(80.0),
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.0),
+                top: Val::Vh(7.0),
+                display: Display::Flex,
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(10.0)),
+                row_gap: Val::Px(6.0),
+                overflow: Overflow::scroll_y(),
+                ..Default::default()
+            },
+            BackgroundColor(Color::srgba(0.05, 0.05, 0.1, 0.95)),
+            GlobalZIndex(5),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Node {
+                    display: Display::Flex,
+                    justify_content: JustifyContent::SpaceBetween,
+                    ..Default::default()
+                },
+                children![
+                    Text::new("Contracts"),
+                    (
+                        Button,
+                        CloseJournal,
+                        Node {
+                            width: Val::Px(25.0),
+                            height: Val::Px(25.0),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        BackgroundColor(Color::WHITE),
+                        children![(Text::new("X"), TextColor(Color::BLACK))],
+                    )
+                ],
+            ));
+            if contracts.0.is_empty() {
+                parent.spawn(Text::new("No contracts signed"));
+            }
+
+            for (i, contract) in contracts.0.iter().enumerate() {
+                let (status, color) = contract_status(contract, &route, &inventories);
+                parent
+                    .spawn((
+                        Node {
+                            display: Display::Flex,
+                            justify_content: JustifyContent::SpaceBetween,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::all(Val::Px(6.0)),
+                            ..Default::default()
+                        },
+                        BackgroundColor(color),
+                        ContractRow(i),
+                    ))
+                    .with_children(|parent| {
+                        let font = TextFont {
+                            font_size: 14.0,
+                            ..Default::default()
+                        };
+                        parent
+                            .spawn(Node {
+                                display: Display::Flex,
+                                flex_direction: FlexDirection::Column,
+                                ..Default::default()
+                            })
+                            .with_children(|parent| {
+                                parent
+                                    .spawn(Node {
+                                        display: Display::Flex,
+                                        align_items: AlignItems::Center,
+                                        column_gap: Val::Px(5.0),
+                                        ..Default::default()
+                                    })
+                                    .with_children(|parent| {
+                                        spawn_items(
+                                            parent,
+                                            &items,
+                                            std::slice::from_ref(&contract.required),
+                                            font.clone(),
+                                        );
+                                        parent.spawn((Text::new("for"), font.clone()));
+                                        spawn_items(
+                                            parent,
+                                            &items,
+                                            std::slice::from_ref(&contract.reward),
+                                            font.clone(),
+                                        );
+                                    });
+                                parent.spawn((Text::new(status), font, ContractProgress(i)));
+                            });
+                        parent.spawn((
+                            Button,
+                            AbandonButton(i),
+                            Node {
+                                padding: UiRect::horizontal(Val::Px(5.0)),
+                                ..Default::default()
+                            },
+                            BackgroundColor(Color::WHITE),
+                            children![(Text::new("Abandon"), TextColor(Color::BLACK))],
+                        ));
+                    });
+            }
+        });
+}
+
+/// Signing, abandoning or settling contracts changes what is listed, so the journal is
+/// rebuilt then. Goods coming and going only change the progress, see [`update_progress`].
+fn redraw_journal(
+    mut commands: Commands,
+    journals: Query<Entity, With<Journal>>,
+    contracts: Res<ActiveContracts>,
+    route: Res<RouteMap>,
+    inventories: Query<&Inventory>,
+    items: Items,
+) {
+    if !contracts.is_changed() {
+        return;
+    }
+    for journal in &journals {
+        commands.entity(journal).despawn();
+    }
+    spawn_journal(commands, contracts, route, inventories, items);
+}
+
+fn any_inventory_changed(changed: Query<(), Changed<Inventory>>) -> bool {
+    !changed.is_empty()
+}
+
+/// Production, meals, ammo and trades change what the train carries all the time, so the
+/// progress is updated in place rather than rebuilding the journal and losing its scroll.
+fn update_progress(
+    contracts: Res<ActiveContracts>,
+    route: Res<RouteMap>,
+    inventories: Query<&Inventory>,
+    mut rows: Query<(&ContractRow, &mut BackgroundColor)>,
+    mut progress: Query<(&ContractProgress, &mut Text)>,
+) {
+    let status = contracts
+        .0
+        .iter()
+        .map(|it| contract_status(it, &route, &inventories))
+        .collect::<Vec<_>>();
+    for (ContractRow(i), mut background) in &mut rows {
+        if let Some((_, color)) = status.get(*i)
+            && background.0 != *color
+        {
+            background.0 = *color;
+        }
+    }
+    for (ContractProgress(i), mut text) in &mut progress {
+        if let Some((line, _)) = status.get(*i)
+            && text.0 != *line
+        {
+            text.0 = line.clone();
+        }
+    }
+}
+
+/// Walking away from a contract counts as failing it.
+fn abandon_buttons(
+    interaction_query: Query<(&Interaction, &AbandonButton), Changed<Interaction>>,
+    mut contracts: ResMut<ActiveContracts>,
+    mut reputation: ResMut<Reputation>,
+    items: Items,
+) {
+    for (interaction, AbandonButton(i)) in &interaction_query {
+        if *interaction != Interaction::Pressed || *i >= contracts.0.len() {
+            continue;
+        }
+        let contract = contracts.0.remove(*i);
+        info!(
+            "Abandoned contract for {}",
+            items.name(&contract.required.0)
+        );
+        if let Some(issuer) = contract.issuer {
+            reputation.record(issuer, false);
+        }
+    }
+}
+
+fn close_journal(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<CloseJournal>)>,
+    mut next_state: ResMut<NextState<InMenu>>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            next_state.set(InMenu::None);
+        }
+    }
+}
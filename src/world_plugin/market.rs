@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::seq::IteratorRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState, InGameState,
+    resources_plugin::{
+        Holds, Inventory, Item, ItemCategory, ItemRegistry, Items, spawn_items, store_all,
+        take_items, total_owned,
+    },
+    train_plugin::TrainState,
+    ui_state::InMenu,
+};
+
+use super::{CurrentStop, GameWorld, NumberedStop, Stop, route_map::RouteMap};
+
+/// How much cheaper a town sells what it makes.
+const PRODUCED_FACTOR: f32 = 0.5;
+/// How much more a town pays for what it is short of.
+const WANTED_FACTOR: f32 = 1.6;
+/// Towns sell for a little more than they buy, so trading back and forth in one town loses money.
+const SPREAD: f32 = 0.1;
+/// How far every unit bought or sold pushes the price.
+const PRICE_STEP: f32 = 0.02;
+/// How quickly prices settle back to normal, per second.
+const RECOVERY_RATE: f32 = 0.05;
+
+const PRODUCED_COLOR: Color = Color::srgb(0.1, 0.5, 0.1);
+const WANTED_COLOR: Color = Color::srgb(0.6, 0.1, 0.1);
+const UNAVAILABLE_COLOR: Color = Color::srgb(0.6, 0.6, 0.6);
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Market {
+    /// The id of the town on the [`RouteMap`].
+    pub town: usize,
+    pub produces: Item,
+    pub wants: Vec<Item>,
+    /// How far trading has pushed each price, 0 being the town's usual price.
+    pub pressure: HashMap<Item, f32>,
+}
+
+impl Market {
+    /// What a town makes and needs only depends on the seed, so the route map can show it
+    /// before the train gets there.
+    pub fn generate(world: &GameWorld, town: usize, items: &ItemRegistry) -> Self {
+        let mut rng = world.town_rng(town);
+        let mut goods = traded(items).choose_multiple(&mut rng, 3);
+        let produces = goods.pop().unwrap();
+        Self {
+            town,
+            produces,
+            wants: goods,
+            pressure: HashMap::new(),
+        }
+    }
+
+    fn unit_price(&self, items: &ItemRegistry, item: &Item, pressure: f32) -> f32 {
+        let factor = if *item == self.produces {
+            PRODUCED_FACTOR
+        } else if self.wants.contains(item) {
+            WANTED_FACTOR
+        } else {
+            1.0
+        };
+        items.base_price(item) * factor * (1.0 + pressure)
+    }
+
+    fn pressure(&self, item: &Item) -> f32 {
+        self.pressure.get(item).copied().unwrap_or(0.0)
+    }
+
+    /// What buying `amount` costs, every unit a little dearer than the last.
+    pub fn buy_price(&self, items: &ItemRegistry, item: &Item, amount: usize) -> usize {
+        let pressure = self.pressure(item);
+        let total = (0..amount)
+            .map(|i| {
+                self.unit_price(items, item, pressure + i as f32 * PRICE_STEP) * (1.0 + SPREAD)
+            })
+            .sum::<f32>();
+        (total.ceil() as usize).max(1)
+    }
+
+    /// What selling `amount` pays, every unit a little cheaper than the last.
+    pub fn sell_price(&self, items: &ItemRegistry, item: &Item, amount: usize) -> usize {
+        let pressure = self.pressure(item);
+        let total = (0..amount)
+            .map(|i| {
+                self.unit_price(items, item, pressure - i as f32 * PRICE_STEP) * (1.0 - SPREAD)
+            })
+            .sum::<f32>();
+        total.floor() as usize
+    }
+
+    fn trade(&mut self, item: &Item, amount: isize) {
+        let pressure = self.pressure.entry(item.clone()).or_insert(0.0);
+        *pressure = (*pressure + amount as f32 * PRICE_STEP).clamp(-0.8, 2.0);
+    }
+}
+
+/// Everything markets deal in, which is every item but currency.
+fn traded(items: &ItemRegistry) -> impl Iterator<Item = Item> {
+    items
+        .iter()
+        .filter(|it| it.category != ItemCategory::Currency)
+        .map(|it| it.id.clone())
+}
+
+/// How many units of `item` every market button trades at once.
+fn trade_amount(items: &ItemRegistry, item: &Item) -> usize {
+    items.get(item).map_or(1, |it| it.stack_size)
+}
+
+/// The market of the town the train is stopped at.
+#[derive(Resource, Default)]
+pub struct TownMarket(pub Option<Market>);
+
+#[derive(Component)]
+struct MarketHeader;
+
+#[derive(Component, Clone)]
+struct MarketRow(Item);
+
+#[derive(Component)]
+struct MarketButton {
+    item: Item,
+    buy: bool,
+}
+
+pub fn market_plugin(app: &mut App) {
+    app.init_resource::<TownMarket>()
+        .add_systems(
+            OnEnter(TrainState::Stopped),
+            open_market.run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(
+            OnExit(TrainState::Stopped),
+            |mut market: ResMut<TownMarket>| market.0 = None,
+        )
+        .add_systems(
+            OnExit(GameState::InGame),
+            |mut market: ResMut<TownMarket>| market.0 = None,
+        )
+        .add_systems(
+            Update,
+            (
+                recover_prices,
+                (market_buttons, update_market).run_if(in_state(InMenu::StopMenu)),
+            )
+                .chain()
+                .run_if(in_state(GameState::InGame).and(in_state(InGameState::Running))),
+        );
+}
+
+fn open_market(
+    current_stop: Res<CurrentStop>,
+    route: Res<RouteMap>,
+    world: Res<GameWorld>,
+    mut market: ResMut<TownMarket>,
+    items: Items,
+) {
+    let Some(NumberedStop(Stop::Town, _)) = current_stop.0 else {
+        return;
+    };
+    // a loaded game brings its own market along
+    if market.0.as_ref().is_some_and(|it| it.town == route.current) {
+        return;
+    }
+    market.0 = Some(Market::generate(&world, route.current, &items));
+}
+
+fn recover_prices(mut market: ResMut<TownMarket>, time: Res<Time>) {
+    let Some(market) = &mut market.0 else {
+        return;
+    };
+    for pressure in market.pressure.values_mut() {
+        *pressure *= 1.0 - (RECOVERY_RATE * time.delta_secs()).min(1.0);
+    }
+}
+
+/// The market tab of the stop menu, filled in by [`update_market`].
+pub(super) fn spawn_market_panel(parent: &mut ChildSpawnerCommands, items: &ItemRegistry) {
+    parent.spawn((MarketHeader, Text::new(""), TextColor(Color::BLACK)));
+    for item in traded(items) {
+        let amount = trade_amount(items, &item);
+        parent
+            .spawn(Node {
+                display: Display::Flex,
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(10.0),
+                ..Default::default()
+            })
+            .with_children(|parent| {
+                spawn_items(
+                    parent,
+                    items,
+                    &[(item.clone(), 0)],
+                    (
+                        MarketRow(item.clone()),
+                        Node {
+                            width: Val::Px(300.0),
+                            ..Default::default()
+                        },
+                        TextColor(Color::BLACK),
+                    ),
+                );
+                for buy in [true, false] {
+                    parent.spawn((
+                        Node {
+                            padding: UiRect::horizontal(Val::Px(5.0)),
+                            ..Default::default()
+                        },
+                        BackgroundColor(Color::srgb(0.85, 0.85, 0.85)),
+                        Button,
+                        MarketButton {
+                            item: item.clone(),
+                            buy,
+                        },
+                        children![(
+                            Text::new(if buy {
+                                format!("Buy {amount}")
+                            } else {
+                                format!("Sell {amount}")
+                            }),
+                            TextColor(Color::BLACK)
+                        )],
+                    ));
+                }
+            });
+    }
+}
+
+/// Bought goods are stored like produce, and only bought if there is room for them. Takings
+/// go to the locomotive.
+fn market_buttons(
+    interaction_query: Query<(&Interaction, &MarketButton), Changed<Interaction>>,
+    mut market: ResMut<TownMarket>,
+    mut holds: Holds,
+    items: Items,
+) {
+    let Some(market) = &mut market.0 else {
+        return;
+    };
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let item = &button.item;
+        let amount = trade_amount(&items, item);
+        let name = items.name(item);
+        if button.buy {
+            let cost = market.buy_price(&items, item, amount);
+            if total_owned(holds.iter().map(|(_, it, ..)| it), &Item::MONEY) < cost {
+                continue;
+            }
+            if !store_all(&mut holds, &[(item.clone(), amount)]) {
+                warn!("No room on the train for {amount} {name}");
+                continue;
+            }
+            take_from_locomotive_first(&mut holds, &Item::MONEY, cost);
+            market.trade(item, amount as isize);
+            info!("Bought {amount} {name} for {cost} Money");
+        } else {
+            if total_owned(holds.iter().map(|(_, it, ..)| it), item) < amount {
+                continue;
+            }
+            let takings = market.sell_price(&items, item, amount);
+            take_from_locomotive_first(&mut holds, item, amount);
+            for (_, mut inventory, .., is_locomotive) in &mut holds {
+                if is_locomotive {
+                    *inventory.items.entry(Item::MONEY).or_insert(0) += takings;
+                }
+            }
+            market.trade(item, -(amount as isize));
+            info!("Sold {amount} {name} for {takings} Money");
+        }
+    }
+}
+
+fn take_from_locomotive_first(holds: &mut Holds, item: &Item, amount: usize) {
+    let left = take_items(
+        holds
+            .iter_mut()
+            .filter(|(.., is_locomotive)| *is_locomotive)
+            .map(|(_, it, ..)| it),
+        item,
+        amount,
+    );
+    take_items(
+        holds
+            .iter_mut()
+            .filter(|(.., is_locomotive)| !is_locomotive)
+            .map(|(_, it, ..)| it),
+        item,
+        left,
+    );
+}
+
+fn update_market(
+    market: Res<TownMarket>,
+    inventories: Query<&Inventory>,
+    mut header: Single<&mut Text, With<MarketHeader>>,
+    mut rows: Query<(&MarketRow, &mut Text, &mut TextColor), Without<MarketHeader>>,
+    buttons: Query<(&MarketButton, &Children)>,
+    mut text_colors: Query<&mut TextColor, (Without<MarketRow>, Without<MarketHeader>)>,
+    items: Items,
+) {
+    let Some(market) = &market.0 else {
+        ***header = "This stop has no market".to_string();
+        return;
+    };
+    let money = total_owned(inventories.iter(), &Item::MONEY);
+    ***header = format!(
+        "Money: {money}    Makes {}, needs {}",
+        items.name(&market.produces),
+        market
+            .wants
+            .iter()
+            .map(|it| items.name(it))
+            .collect::<Vec<_>>()
+            .join(" and ")
+    );
+
+    for (MarketRow(item), mut text, mut color) in &mut rows {
+        let amount = trade_amount(&items, item);
+        **text = format!(
+            "{}x{}    buy {}  sell {}",
+            items.name(item),
+            total_owned(inventories.iter(), item),
+            market.buy_price(&items, item, amount),
+            market.sell_price(&items, item, amount)
+        );
+        color.0 = if *item == market.produces {
+            PRODUCED_COLOR
+        } else if market.wants.contains(item) {
+            WANTED_COLOR
+        } else {
+            Color::BLACK
+        };
+    }
+
+    for (button, children) in &buttons {
+        let amount = trade_amount(&items, &button.item);
+        let available = if button.buy {
+            money >= market.buy_price(&items, &button.item, amount)
+        } else {
+            total_owned(inventories.iter(), &button.item) >= amount
+        };
+        let mut text_color = text_colors.get_mut(children[0]).unwrap();
+        text_color.0 = if available {
+            Color::BLACK
+        } else {
+            UNAVAILABLE_COLOR
+        };
+    }
+}
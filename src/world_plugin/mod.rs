@@ -1,43 +1,58 @@
+use std::hash::{BuildHasher, Hasher, RandomState};
+
 use bevy::prelude::*;
 use rand::{
     Rng, SeedableRng,
     seq::{IndexedMutRandom, IndexedRandom},
 };
+use serde::{Deserialize, Serialize};
 
 use crate::{
     GameState, ImageAssets, InGameState,
     train_plugin::{Train, TrainState, TrainStats},
     ui_state::InMenu,
     world_plugin::{
+        difficulty::Difficulty,
         goblin_spawner::{GoblinSpawner, GoblinType, spawn_goblins},
+        journal::journal_plugin,
+        market::market_plugin,
         progress_bar_plugin::progress_bar_plugin,
+        reputation::reputation_plugin,
+        route_map::{RouteMap, route_map_plugin},
     },
 };
 
-mod goblin_spawner;
-mod progress_bar_plugin;
+pub mod difficulty;
+pub mod goblin_spawner;
+pub mod journal;
+pub mod market;
+pub mod progress_bar_plugin;
+pub mod reputation;
+pub mod route_map;
+pub mod settlement;
 pub mod stop_plugin;
 
 #[derive(Component)]
 #[require(Pickable::default())]
 pub struct WorldClickable;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Stop {
     Town,
     GoblinAttack { waves: Vec<Vec<GoblinType>> },
     Initial,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct NumberedStop(pub Stop, pub usize);
 
 impl Stop {
-    fn spawn_stop(&self, mut commands: Commands, distance: f32, image_assets: Res<ImageAssets>) {
+    pub fn spawn_stop(&self, commands: &mut Commands, distance: f32, image_assets: &ImageAssets) {
         match self {
             Stop::Town => {
                 commands
                     .spawn((
+                        StateScoped(GameState::InGame),
                         NextStopImage,
                         Transform::from_xyz(-distance * METERS_PER_UNIT, 0., -10.),
                         WorldObject(distance),
@@ -67,6 +82,7 @@ impl Stop {
             Stop::Initial => {}
             Stop::GoblinAttack { waves } => {
                 commands.spawn((
+                    StateScoped(GameState::InGame),
                     NextStopImage,
                     Transform::from_xyz(-distance * METERS_PER_UNIT, 0., -10.),
                     WorldObject(distance),
@@ -92,12 +108,12 @@ impl Stop {
         }
     }
 
-    fn generate_random<R: Rng>(rng: &mut R, current_stop: &CurrentStop) -> Self {
+    fn generate_random<R: Rng>(rng: &mut R, current_stop: &CurrentStop, level: f32) -> Self {
         let mut stops: [(&mut dyn FnMut(&mut R) -> Stop, u32); 2] = [
             (&mut |_| Stop::Town, 5),
             (
                 &mut |rng| Stop::GoblinAttack {
-                    waves: generate_waves(rng),
+                    waves: generate_waves(rng, level),
                 },
                 1,
             ),
@@ -151,21 +167,87 @@ fn generate_town_name(rng: &mut impl Rng) -> String {
     out
 }
 
-fn generate_waves(rng: &mut impl Rng) -> Vec<Vec<GoblinType>> {
-    let waves = rng.random_range(1..=10);
+fn generate_waves(rng: &mut impl Rng, level: f32) -> Vec<Vec<GoblinType>> {
+    let waves = rng.random_range(Difficulty::wave_count(level));
+    let types = GoblinType::iterator().collect::<Vec<_>>();
 
-    (0..waves)
+    let mut waves = (0..waves)
         .map(|_| {
-            let num = rng.random_range(1..=10);
-
-            (0..num).map(|_| GoblinType::Basic).collect::<Vec<_>>()
+            let num = rng.random_range(Difficulty::wave_size(level));
+
+            (0..num)
+                .map(|_| {
+                    types
+                        .choose_weighted(rng, |it| it.weight_at(level))
+                        .unwrap()
+                        .clone()
+                })
+                .collect::<Vec<_>>()
         })
-        .collect::<Vec<_>>()
+        .collect::<Vec<_>>();
+    if Difficulty::has_boss(level) {
+        waves.last_mut().unwrap().insert(0, GoblinType::Boss);
+    }
+    waves
 }
 
 #[derive(Resource)]
 pub struct GameWorld {
-    rng: rand_chacha::ChaCha8Rng,
+    pub seed: u64,
+    pub rng: rand_chacha::ChaCha8Rng,
+    pub difficulty: Difficulty,
+}
+
+impl GameWorld {
+    pub fn new(seed: u64, difficulty: Difficulty) -> Self {
+        Self {
+            seed,
+            rng: rand_chacha::ChaCha8Rng::seed_from_u64(seed),
+            difficulty,
+        }
+    }
+
+    /// A generator that only depends on the seed and the town on the [`RouteMap`], so what a
+    /// town offers doesn't change with how often its menu is opened or whether the run was
+    /// reloaded. Towns on different branches can share a stop number, so it goes by town.
+    pub fn town_rng(&self, town_id: usize) -> rand_chacha::ChaCha8Rng {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_stream((1 << 32) | town_id as u64);
+        rng
+    }
+
+    /// Like [`Self::town_rng`] but for the `recruit`th crew member to join.
+    pub fn recruit_rng(&self, recruit: usize) -> rand_chacha::ChaCha8Rng {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_stream((2 << 32) | recruit as u64);
+        rng
+    }
+}
+
+/// The seed the next run is generated from, set from the main menu or `--seed`.
+#[derive(Resource, Clone, Copy)]
+pub struct WorldSeed(pub u64);
+
+impl WorldSeed {
+    pub fn random() -> Self {
+        Self(RandomState::new().build_hasher().finish())
+    }
+
+    pub fn from_args() -> Option<Self> {
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let value = match arg.strip_prefix("--seed") {
+                Some("") => args.next(),
+                Some(value) => value.strip_prefix('=').map(str::to_string),
+                None => continue,
+            };
+            match value.as_deref().map(str::parse) {
+                Some(Ok(seed)) => return Some(Self(seed)),
+                _ => warn!("--seed expects a number, got {value:?}"),
+            }
+        }
+        None
+    }
 }
 
 #[derive(Resource)]
@@ -185,7 +267,13 @@ pub fn world_plugin(app: &mut App) {
     app.add_plugins((
         stop_plugin::stop_plugin,
         progress_bar_plugin::progress_bar_plugin,
+        route_map_plugin,
+        market_plugin,
+        reputation_plugin,
+        journal_plugin,
     ))
+    .insert_resource(WorldSeed::from_args().unwrap_or_else(WorldSeed::random))
+    .init_resource::<Difficulty>()
     .add_systems(OnEnter(GameState::Loading), generate_world)
     .add_systems(
         FixedUpdate,
@@ -204,29 +292,38 @@ pub fn world_plugin(app: &mut App) {
     .add_observer(
         |_trigger: Trigger<GenerateNextStop>,
          mut next_stop: ResMut<NextStop>,
-         current_stop: Res<CurrentStop>,
-         mut game_world: ResMut<GameWorld>,
-         train: Query<&Train>| {
-            *next_stop = generate_next_stop(
-                &mut game_world.rng,
-                train.single().unwrap().distance,
-                &current_stop,
-            );
+         mut route: ResMut<RouteMap>,
+         mut game_world: ResMut<GameWorld>| {
+            let difficulty = game_world.difficulty;
+            route.advance(&mut game_world.rng, difficulty);
+            *next_stop = route.next_stop();
         },
     );
 }
 
-fn generate_world(mut commands: Commands) {
-    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(460);
+pub(crate) fn generate_world(
+    mut commands: Commands,
+    seed: Res<WorldSeed>,
+    difficulty: Res<Difficulty>,
+) {
+    let mut world = GameWorld::new(seed.0, *difficulty);
+    info!(
+        "Generating world with seed {} on {}",
+        world.seed,
+        difficulty.name()
+    );
 
     commands.insert_resource(CurrentStop(Some(NumberedStop(Stop::Initial, 0))));
-    commands.insert_resource(generate_next_stop(&mut rng, 0., &CurrentStop(None)));
+    let route = RouteMap::new(&mut world.rng, *difficulty);
+    commands.insert_resource(route.next_stop());
+    commands.insert_resource(route);
 
-    commands.insert_resource(GameWorld { rng });
+    commands.insert_resource(world);
 }
 
 fn generate_next_stop(
     rng: &mut impl Rng,
+    difficulty: Difficulty,
     current_distance: f32,
     current_stop: &CurrentStop,
 ) -> NextStop {
@@ -234,24 +331,22 @@ fn generate_next_stop(
         60.0..=140.0, /*units now in meters but i made these very small to make it easy to test*/
     ) + current_distance;
     info!("Random f32: {}", distance);
-    let stop = Stop::generate_random(rng, current_stop);
+    let stop_number = current_stop
+        .0
+        .clone()
+        .map(|it| {
+            if let Stop::GoblinAttack { .. } = it.0 {
+                it.1
+            } else {
+                it.1 + 1
+            } //ensure contracts dont expire on goblin stops
+        })
+        .unwrap_or(1);
+    let stop = Stop::generate_random(rng, current_stop, difficulty.level(stop_number, distance));
 
     NextStop {
         name: stop.generate_name(rng),
-        stop: NumberedStop(
-            stop,
-            current_stop
-                .0
-                .clone()
-                .map(|it| {
-                    if let Stop::GoblinAttack { .. } = it.0 {
-                        it.1
-                    } else {
-                        it.1 + 1
-                    } //ensure contracts dont expire on goblin stops
-                })
-                .unwrap_or(1),
-        ),
+        stop: NumberedStop(stop, stop_number),
         distance,
         spawned: false,
     }
@@ -266,7 +361,7 @@ struct NextStopImage;
 const METERS_PER_UNIT: f32 = 100.0;
 
 fn spawn_stop_assets(
-    commands: Commands,
+    mut commands: Commands,
     train: Query<&Train>,
     mut next_stop: ResMut<NextStop>,
     image_assets: Res<ImageAssets>,
@@ -281,7 +376,7 @@ fn spawn_stop_assets(
         next_stop
             .stop
             .0
-            .spawn_stop(commands, next_stop.distance, image_assets);
+            .spawn_stop(&mut commands, next_stop.distance, &image_assets);
     }
 }
 
@@ -325,6 +420,7 @@ const NUM_RAILS: usize = 8;
 fn spawn_rails(mut commands: Commands, image_assets: Res<ImageAssets>) {
     for i in 0..NUM_RAILS {
         commands.spawn((
+            StateScoped(GameState::InGame),
             Sprite::from_image(image_assets.rail.clone()),
             Transform::default(),
             WorldObject((i as f32 - 4.) * RAIL_WIDTH),
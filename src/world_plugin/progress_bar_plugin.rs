@@ -17,6 +17,15 @@ pub fn progress_bar_plugin(app: &mut App) {
         swapped: false,
     })
     .insert_resource(LastStopDist(0.))
+    .add_systems(
+        OnExit(GameState::InGame),
+        |mut anim_timer: ResMut<AnimTimer>, mut last_stop_dist: ResMut<LastStopDist>| {
+            anim_timer.time = 20.;
+            anim_timer.right_slide_amount = 0.;
+            anim_timer.swapped = false;
+            last_stop_dist.0 = 0.;
+        },
+    )
     .add_systems(OnEnter(GameState::InGame), spawn_progress_bar)
     .add_systems(
         Update,
@@ -52,10 +61,11 @@ struct AnimTimer {
 }
 
 #[derive(Resource)]
-struct LastStopDist(f32);
+pub struct LastStopDist(pub f32);
 
 fn spawn_progress_bar(mut commands: Commands, image_assets: Res<ImageAssets>) {
     commands.spawn((
+        StateScoped(GameState::InGame),
         Node {
             width: Val::Vw(60.0),
             height: Val::Vh(2.),
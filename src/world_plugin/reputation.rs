@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState,
+    resources_plugin::{Item, ItemRegistry},
+};
+
+const MAX_REPUTATION: i32 = 10;
+/// Gained with a faction for every contract of theirs that is delivered.
+const SUCCESS_GAIN: i32 = 2;
+/// Lost with a faction for every contract of theirs that falls through.
+const FAILURE_LOSS: i32 = 3;
+/// From here on a faction trusts the train enough to waive deposits.
+pub const TRUSTED: i32 = 4;
+
+/// Towns belong to whoever runs what they make, so the player's standing follows them down
+/// the line even though no town is ever visited twice.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Faction {
+    FarmersUnion,
+    MinersGuild,
+    Foundry,
+}
+
+impl Faction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Faction::FarmersUnion => "Farmers' Union",
+            Faction::MinersGuild => "Miners' Guild",
+            Faction::Foundry => "Foundry",
+        }
+    }
+
+    /// The faction of a town that makes `item`, as listed in the [`ItemRegistry`]. Towns
+    /// making anything unlisted fall to the Foundry.
+    pub fn of(item: &Item, items: &ItemRegistry) -> Self {
+        items.get(item).map_or(Faction::Foundry, |it| it.faction)
+    }
+}
+
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct Reputation(pub HashMap<Faction, i32>);
+
+impl Reputation {
+    pub fn get(&self, faction: Faction) -> i32 {
+        self.0.get(&faction).copied().unwrap_or(0)
+    }
+
+    pub fn standing(&self, faction: Faction) -> &'static str {
+        match self.get(faction) {
+            ..=-5 => "Distrusted",
+            -4..=-1 => "Wary",
+            0..TRUSTED => "Neutral",
+            TRUSTED..=7 => "Trusted",
+            _ => "Honoured",
+        }
+    }
+
+    /// Multiplies what the faction's contracts pay.
+    pub fn reward_factor(&self, faction: Faction) -> f32 {
+        1.0 + self.get(faction) as f32 * 0.05
+    }
+
+    /// Extra stops the faction allows for its contracts, negative once they stop trusting the
+    /// train.
+    pub fn deadline_slack(&self, faction: Faction) -> isize {
+        match self.get(faction) {
+            ..=-5 => -1,
+            -4..TRUSTED => 0,
+            _ => 1,
+        }
+    }
+
+    /// The share of the reward a faction holds back until its contract is delivered.
+    pub fn deposit_share(&self, faction: Faction) -> f32 {
+        match self.get(faction) {
+            TRUSTED.. => 0.0,
+            0..TRUSTED => 0.1,
+            _ => 0.25,
+        }
+    }
+
+    pub fn record(&mut self, faction: Faction, delivered: bool) {
+        let reputation = self.0.entry(faction).or_insert(0);
+        *reputation = if delivered {
+            *reputation + SUCCESS_GAIN
+        } else {
+            *reputation - FAILURE_LOSS
+        }
+        .clamp(-MAX_REPUTATION, MAX_REPUTATION);
+        info!(
+            "Reputation with the {} is now {}",
+            faction.name(),
+            reputation
+        );
+    }
+}
+
+pub fn reputation_plugin(app: &mut App) {
+    app.init_resource::<Reputation>().add_systems(
+        OnExit(GameState::InGame),
+        |mut reputation: ResMut<Reputation>| reputation.0.clear(),
+    );
+}
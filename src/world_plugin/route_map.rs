@@ -0,0 +1,384 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    GameState, InGameState,
+    resources_plugin::{ItemRegistry, Items, spawn_items},
+    train_plugin::{Train, TrainState},
+    ui_state::InMenu,
+};
+
+use super::{
+    CurrentStop, GameWorld, NextStop, NextStopImage, NumberedStop, Stop, WorldObject,
+    difficulty::Difficulty, generate_next_stop, market::Market, stop_plugin::ActiveContracts,
+};
+
+/// How many stops past the current one are known at any time.
+pub const LOOKAHEAD: usize = 8;
+/// How likely a stop is to split into two lines.
+const JUNCTION_CHANCE: f64 = 0.25;
+
+const TOWN_COLOR: Color = Color::srgb(0.3, 0.5, 0.3);
+const AMBUSH_COLOR: Color = Color::srgb(0.6, 0.2, 0.2);
+const CHOSEN_BORDER: Color = Color::srgb(1.0, 0.85, 0.2);
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RouteStop {
+    pub id: usize,
+    pub stop: NumberedStop,
+    pub distance: f32,
+    pub name: String,
+    /// Ids of the stops this one leads to, more than one makes it a junction.
+    pub next: Vec<usize>,
+}
+
+/// The stops ahead of the train as a tree rooted at the stop it is at or last left.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct RouteMap {
+    pub stops: Vec<RouteStop>,
+    pub current: usize,
+    /// The branch out of `current` the train takes next.
+    pub chosen: usize,
+    pub next_id: usize,
+}
+
+impl RouteMap {
+    pub fn new(rng: &mut impl Rng, difficulty: Difficulty) -> Self {
+        let mut route = Self {
+            stops: vec![RouteStop {
+                id: 0,
+                stop: NumberedStop(Stop::Initial, 0),
+                distance: 0.0,
+                name: String::new(),
+                next: Vec::new(),
+            }],
+            current: 0,
+            chosen: 0,
+            next_id: 1,
+        };
+        route.extend(rng, difficulty);
+        route
+    }
+
+    pub fn get(&self, id: usize) -> Option<&RouteStop> {
+        self.stops.iter().find(|it| it.id == id)
+    }
+
+    pub fn branches(&self) -> &[usize] {
+        &self.get(self.current).unwrap().next
+    }
+
+    pub fn next_stop(&self) -> NextStop {
+        let stop = self.get(self.chosen).unwrap();
+        NextStop {
+            stop: stop.stop.clone(),
+            distance: stop.distance,
+            spawned: false,
+            name: stop.name.clone(),
+        }
+    }
+
+    /// Generates stops until everything up to [`LOOKAHEAD`] stops ahead is known.
+    pub fn extend(&mut self, rng: &mut impl Rng, difficulty: Difficulty) {
+        let mut to_visit = vec![(self.current, 0)];
+        while let Some((id, depth)) = to_visit.pop() {
+            if depth >= LOOKAHEAD {
+                continue;
+            }
+            let index = self.stops.iter().position(|it| it.id == id).unwrap();
+            if self.stops[index].next.is_empty() {
+                let from = self.stops[index].clone();
+                let branches = if rng.random_bool(JUNCTION_CHANCE) {
+                    2
+                } else {
+                    1
+                };
+                for _ in 0..branches {
+                    let next_stop = generate_next_stop(
+                        rng,
+                        difficulty,
+                        from.distance,
+                        &CurrentStop(Some(from.stop.clone())),
+                    );
+                    self.stops.push(RouteStop {
+                        id: self.next_id,
+                        stop: next_stop.stop,
+                        distance: next_stop.distance,
+                        name: next_stop.name,
+                        next: Vec::new(),
+                    });
+                    self.stops[index].next.push(self.next_id);
+                    self.next_id += 1;
+                }
+            }
+            to_visit.extend(self.stops[index].next.iter().map(|it| (*it, depth + 1)));
+        }
+
+        if !self.branches().contains(&self.chosen) {
+            self.chosen = self.branches()[0];
+        }
+    }
+
+    /// Moves on to the chosen branch and forgets the lines that weren't taken.
+    pub fn advance(&mut self, rng: &mut impl Rng, difficulty: Difficulty) {
+        self.current = self.chosen;
+        let mut reachable = vec![self.current];
+        let mut i = 0;
+        while i < reachable.len() {
+            reachable.extend(self.get(reachable[i]).unwrap().next.iter().copied());
+            i += 1;
+        }
+        self.stops.retain(|it| reachable.contains(&it.id));
+        self.extend(rng, difficulty);
+    }
+
+    /// Towns still ahead that carry the given stop number, on any branch.
+    pub fn towns_numbered(&self, stop_number: usize) -> impl Iterator<Item = &RouteStop> {
+        self.stops.iter().filter(move |it| {
+            it.id != self.current
+                && matches!(it.stop, NumberedStop(Stop::Town, n) if n == stop_number)
+        })
+    }
+}
+
+#[derive(Component)]
+struct RouteMapView;
+
+#[derive(Component)]
+struct CloseRouteMap;
+
+#[derive(Component)]
+struct BranchButton(usize);
+
+pub fn route_map_plugin(app: &mut App) {
+    app.add_systems(OnEnter(InMenu::RouteMap), spawn_route_map)
+        .add_systems(
+            Update,
+            (
+                redraw_route_map.run_if(resource_changed::<RouteMap>),
+                close_route_map,
+                choose_branch.run_if(in_state(TrainState::Stopped)),
+            )
+                .run_if(
+                    in_state(GameState::InGame)
+                        .and(in_state(InGameState::Running))
+                        .and(in_state(InMenu::RouteMap)),
+                ),
+        );
+}
+
+fn spawn_route_map(
+    mut commands: Commands,
+    route: Res<RouteMap>,
+    contracts: Res<ActiveContracts>,
+    world: Res<GameWorld>,
+    train: Single<&Train>,
+    items: Items,
+) {
+    commands
+        .spawn((
+            StateScoped(InMenu::RouteMap),
+            RouteMapView,
+            Node {
+                width: Val::Vw(100.0),
+                height: Val::Vh(100.0),
+                position_type: PositionType::Absolute,
+                display: Display::Flex,
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(20.0)),
+                row_gap: Val::Px(10.0),
+                ..Default::default()
+            },
+            BackgroundColor(Color::srgba(0.05, 0.05, 0.1, 0.95)),
+            GlobalZIndex(5),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Node {
+                    display: Display::Flex,
+                    justify_content: JustifyContent::SpaceBetween,
+                    ..Default::default()
+                },
+                children![
+                    Text::new("Route Map - pick a branch while stopped at a junction"),
+                    (
+                        Button,
+                        CloseRouteMap,
+                        Node {
+                            width: Val::Px(25.0),
+                            height: Val::Px(25.0),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        BackgroundColor(Color::WHITE),
+                        children![(Text::new("X"), TextColor(Color::BLACK))],
+                    )
+                ],
+            ));
+            spawn_route_stop(
+                parent,
+                &route,
+                &contracts,
+                &world,
+                &items,
+                train.distance,
+                route.current,
+            );
+        });
+}
+
+/// Lays a stop out with the stops it leads to stacked to its right, so each junction fans out.
+fn spawn_route_stop(
+    parent: &mut ChildSpawnerCommands,
+    route: &RouteMap,
+    contracts: &ActiveContracts,
+    world: &GameWorld,
+    items: &ItemRegistry,
+    train_distance: f32,
+    id: usize,
+) {
+    let stop = route.get(id).unwrap();
+    let (kind, color) = match stop.stop.0 {
+        Stop::Town => ("Town", TOWN_COLOR),
+        Stop::GoblinAttack { .. } => ("Goblin Ambush", AMBUSH_COLOR),
+        Stop::Initial => ("Depot", TOWN_COLOR),
+    };
+    let mut label = if id == route.current {
+        format!("{kind}\n(you are here)")
+    } else {
+        format!(
+            "{}\n{kind}, stop {}\n{:.0}m ahead",
+            stop.name,
+            stop.stop.1,
+            stop.distance - train_distance
+        )
+    };
+    if let Stop::Town = stop.stop.0 {
+        let market = Market::generate(world, id, items);
+        label.push_str(&format!(
+            "\nMakes {}, needs {}",
+            items.name(&market.produces),
+            market
+                .wants
+                .iter()
+                .map(|it| items.name(it))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    let deliveries = contracts
+        .0
+        .iter()
+        .filter(|it| it.destination.as_ref().is_some_and(|it| it.id == id))
+        .map(|it| it.required.clone())
+        .collect::<Vec<_>>();
+    let on_route = id == route.current || id == route.chosen;
+
+    parent
+        .spawn(Node {
+            display: Display::Flex,
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(8.0),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            let font = TextFont {
+                font_size: 11.0,
+                ..Default::default()
+            };
+            let mut stop_box = parent.spawn((
+                Node {
+                    width: Val::Px(130.0),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(4.0)),
+                    border: UiRect::all(Val::Px(2.0)),
+                    ..Default::default()
+                },
+                BackgroundColor(color),
+                BorderColor(if on_route { CHOSEN_BORDER } else { Color::NONE }),
+            ));
+            stop_box.with_children(|parent| {
+                parent.spawn((Text::new(label), font.clone()));
+                if !deliveries.is_empty() {
+                    parent.spawn((Text::new("Deliver"), font.clone()));
+                    spawn_items(parent, items, &deliveries, font);
+                }
+            });
+            if route.branches().len() > 1 && route.branches().contains(&id) {
+                stop_box.insert((Button, BranchButton(id)));
+            }
+
+            parent
+                .spawn(Node {
+                    display: Display::Flex,
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(6.0),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for next in &stop.next {
+                        spawn_route_stop(
+                            parent,
+                            route,
+                            contracts,
+                            world,
+                            items,
+                            train_distance,
+                            *next,
+                        );
+                    }
+                });
+        });
+}
+
+fn redraw_route_map(
+    mut commands: Commands,
+    views: Query<Entity, With<RouteMapView>>,
+    route: Res<RouteMap>,
+    contracts: Res<ActiveContracts>,
+    world: Res<GameWorld>,
+    train: Single<&Train>,
+    items: Items,
+) {
+    for view in &views {
+        commands.entity(view).despawn();
+    }
+    spawn_route_map(commands, route, contracts, world, train, items);
+}
+
+fn close_route_map(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<CloseRouteMap>)>,
+    mut next_state: ResMut<NextState<InMenu>>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            next_state.set(InMenu::None);
+        }
+    }
+}
+
+/// Switching lines swaps out the next stop, including the scenery already put up for it.
+fn choose_branch(
+    interaction_query: Query<(&Interaction, &BranchButton), Changed<Interaction>>,
+    mut route: ResMut<RouteMap>,
+    mut next_stop: ResMut<NextStop>,
+    stop_images: Query<(Entity, &WorldObject), With<NextStopImage>>,
+    train: Single<&Train>,
+    mut commands: Commands,
+) {
+    for (interaction, BranchButton(id)) in &interaction_query {
+        if *interaction != Interaction::Pressed || route.chosen == *id {
+            continue;
+        }
+        route.chosen = *id;
+        *next_stop = route.next_stop();
+        info!("Taking the line to {}", next_stop.name);
+        for (entity, world_object) in &stop_images {
+            if world_object.0 > train.distance {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use crate::resources_plugin::{AcceptedItems, Capacity, Inventory, Item};
+
+use super::stop_plugin::Contract;
+
+/// One of the train's inventories as contract settlement sees it.
+pub struct Hold<'a> {
+    pub inventory: &'a Inventory,
+    pub accepted: Option<&'a AcceptedItems>,
+    pub capacity: Option<&'a Capacity>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The train stopped at a different town than the one the contract was for.
+    WrongTown,
+    Shortfall {
+        /// Counting what was handed over early.
+        owned: usize,
+    },
+}
+
+/// What handing in the contracts due at a stop comes to. Hold indices refer to the slice
+/// given to [`settle_contracts`].
+#[derive(Debug, Default)]
+pub struct Settlement {
+    pub delivered: Vec<Contract>,
+    pub failed: Vec<(Contract, Failure)>,
+    /// Contracts that aren't due yet, still in the order they were signed.
+    pub pending: Vec<Contract>,
+    /// Goods handed over, as `(hold, item, amount)`.
+    pub taken: Vec<(usize, Item, usize)>,
+    /// Rewards and returned deposits, as `(hold, item, amount)`.
+    pub given: Vec<(usize, Item, usize)>,
+    /// Rewards that didn't fit anywhere on the train.
+    pub left_behind: Vec<(Item, usize)>,
+}
+
+/// Works out which contracts due at `stop_number` are delivered and which fail, in the order
+/// they were signed, so goods handed in for one contract can't also count for the next.
+/// Goods handed over early are credited first and only the rest is taken from the holds.
+/// Rewards fill the first holds with room for them.
+pub fn settle_contracts(
+    contracts: &[Contract],
+    stop_number: usize,
+    town: usize,
+    holds: &[Hold],
+) -> Settlement {
+    let mut inventories = holds
+        .iter()
+        .map(|it| it.inventory.clone())
+        .collect::<Vec<_>>();
+    let mut settlement = Settlement::default();
+
+    for contract in contracts {
+        if contract.stop_number != stop_number {
+            settlement.pending.push(contract.clone());
+            continue;
+        }
+        if contract
+            .destination
+            .as_ref()
+            .is_some_and(|it| it.id != town)
+        {
+            settlement
+                .failed
+                .push((contract.clone(), Failure::WrongTown));
+            continue;
+        }
+
+        let item = &contract.required.0;
+        let outstanding = contract.outstanding();
+        let carried = inventories.iter().map(|it| it.count(item)).sum::<usize>();
+        if carried < outstanding {
+            settlement.failed.push((
+                contract.clone(),
+                Failure::Shortfall {
+                    owned: contract.handed_over + carried,
+                },
+            ));
+            continue;
+        }
+
+        let mut left = outstanding;
+        for (i, inventory) in inventories.iter_mut().enumerate() {
+            let Some(held) = inventory.items.get_mut(item) else {
+                continue;
+            };
+            let taken = (*held).min(left);
+            if taken > 0 {
+                *held -= taken;
+                left -= taken;
+                settlement.taken.push((i, item.clone(), taken));
+            }
+            if left == 0 {
+                break;
+            }
+        }
+
+        for (item, amount) in [contract.reward.clone(), (Item::MONEY, contract.deposit)] {
+            let mut left = amount;
+            for (i, (inventory, hold)) in inventories.iter_mut().zip(holds).enumerate() {
+                if left == 0 {
+                    break;
+                }
+                let given = inventory
+                    .space_for(&item, hold.accepted, hold.capacity)
+                    .min(left);
+                if given > 0 {
+                    *inventory.items.entry(item.clone()).or_insert(0) += given;
+                    left -= given;
+                    settlement.given.push((i, item.clone(), given));
+                }
+            }
+            if left > 0 {
+                settlement.left_behind.push((item, left));
+            }
+        }
+        settlement.delivered.push(contract.clone());
+    }
+    settlement
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world_plugin::stop_plugin::Destination;
+
+    fn inventory(items: &[(Item, usize)]) -> Inventory {
+        Inventory {
+            items: items.iter().cloned().collect(),
+        }
+    }
+
+    fn hold(inventory: &Inventory) -> Hold<'_> {
+        Hold {
+            inventory,
+            accepted: None,
+            capacity: None,
+        }
+    }
+
+    fn contract(required: (Item, usize), reward: (Item, usize), stop_number: usize) -> Contract {
+        Contract {
+            required,
+            reward,
+            stop_number,
+            destination: None,
+            issuer: None,
+            deposit: 0,
+            handed_over: 0,
+        }
+    }
+
+    #[test]
+    fn settles_every_due_contract_and_keeps_the_rest() {
+        let locomotive = inventory(&[(Item::WOOD, 50), (Item::METAL, 10)]);
+        let contracts = [
+            contract((Item::WOOD, 20), (Item::MONEY, 30), 3),
+            contract((Item::METAL, 5), (Item::MONEY, 15), 4),
+            contract((Item::METAL, 10), (Item::GLASS, 4), 3),
+        ];
+
+        let settlement = settle_contracts(&contracts, 3, 0, &[hold(&locomotive)]);
+
+        assert_eq!(settlement.delivered.len(), 2);
+        assert_eq!(settlement.delivered[0].required, (Item::WOOD, 20));
+        assert_eq!(settlement.delivered[1].required, (Item::METAL, 10));
+        assert!(settlement.failed.is_empty());
+        assert_eq!(settlement.pending.len(), 1);
+        assert_eq!(settlement.pending[0].required, (Item::METAL, 5));
+    }
+
+    #[test]
+    fn goods_only_count_towards_one_contract() {
+        let locomotive = inventory(&[(Item::WOOD, 30)]);
+        let contracts = [
+            contract((Item::WOOD, 20), (Item::MONEY, 30), 2),
+            contract((Item::WOOD, 20), (Item::MONEY, 30), 2),
+        ];
+
+        let settlement = settle_contracts(&contracts, 2, 0, &[hold(&locomotive)]);
+
+        assert_eq!(settlement.delivered.len(), 1);
+        assert_eq!(
+            settlement.failed[0].1,
+            Failure::Shortfall { owned: 10 },
+            "the second contract only sees what the first one left"
+        );
+        assert_eq!(settlement.taken, vec![(0, Item::WOOD, 20)]);
+    }
+
+    #[test]
+    fn takes_exactly_what_is_required_across_holds() {
+        let first = inventory(&[(Item::CLAY, 8)]);
+        let second = inventory(&[(Item::CLAY, 8)]);
+        let third = inventory(&[(Item::CLAY, 8)]);
+        let contracts = [contract((Item::CLAY, 12), (Item::MONEY, 10), 1)];
+
+        let settlement = settle_contracts(
+            &contracts,
+            1,
+            0,
+            &[hold(&first), hold(&second), hold(&third)],
+        );
+
+        assert_eq!(
+            settlement.taken,
+            vec![(0, Item::CLAY, 8), (1, Item::CLAY, 4)]
+        );
+    }
+
+    #[test]
+    fn goods_handed_over_early_count_towards_delivery() {
+        let locomotive = inventory(&[(Item::BRICK, 10)]);
+        let mut topped_up = contract((Item::BRICK, 12), (Item::MONEY, 20), 1);
+        topped_up.handed_over = 8;
+        let mut complete = contract((Item::BRICK, 5), (Item::MONEY, 10), 1);
+        complete.handed_over = 5;
+        let mut short = contract((Item::BRICK, 20), (Item::MONEY, 40), 1);
+        short.handed_over = 3;
+
+        let settlement =
+            settle_contracts(&[topped_up, complete, short], 1, 0, &[hold(&locomotive)]);
+
+        assert_eq!(settlement.delivered.len(), 2);
+        assert_eq!(settlement.taken, vec![(0, Item::BRICK, 4)]);
+        assert_eq!(
+            settlement.failed[0].1,
+            Failure::Shortfall { owned: 9 },
+            "what was handed over counts as owned"
+        );
+    }
+
+    #[test]
+    fn failed_contracts_take_nothing() {
+        let locomotive = inventory(&[(Item::named("Ore"), 5)]);
+        let contracts = [contract((Item::named("Ore"), 6), (Item::MONEY, 10), 1)];
+
+        let settlement = settle_contracts(&contracts, 1, 0, &[hold(&locomotive)]);
+
+        assert!(settlement.delivered.is_empty());
+        assert!(settlement.taken.is_empty());
+        assert!(settlement.given.is_empty());
+    }
+
+    #[test]
+    fn rewards_go_into_one_hold_and_respect_what_it_takes() {
+        let tanker = inventory(&[]);
+        let water_only = AcceptedItems(vec![Item::WATER]);
+        let boxcar = inventory(&[(Item::FOOD, 10)]);
+        let locomotive = inventory(&[(Item::FOOD, 10)]);
+        let contracts = [contract((Item::FOOD, 10), (Item::BRICK, 25), 1)];
+
+        let settlement = settle_contracts(
+            &contracts,
+            1,
+            0,
+            &[
+                Hold {
+                    inventory: &tanker,
+                    accepted: Some(&water_only),
+                    capacity: None,
+                },
+                hold(&boxcar),
+                hold(&locomotive),
+            ],
+        );
+
+        assert_eq!(settlement.given, vec![(1, Item::BRICK, 25)]);
+    }
+
+    #[test]
+    fn rewards_spill_over_full_holds_and_the_rest_is_left_behind() {
+        let storage = inventory(&[(Item::WOOD, 15)]);
+        let small = Capacity(20);
+        let shed = inventory(&[]);
+        let tiny = Capacity(10);
+        let contracts = [contract((Item::WOOD, 10), (Item::METAL, 30), 1)];
+
+        let settlement = settle_contracts(
+            &contracts,
+            1,
+            0,
+            &[
+                Hold {
+                    inventory: &storage,
+                    accepted: None,
+                    capacity: Some(&small),
+                },
+                Hold {
+                    inventory: &shed,
+                    accepted: None,
+                    capacity: Some(&tiny),
+                },
+            ],
+        );
+
+        // handing in the wood makes room before the reward arrives
+        assert_eq!(
+            settlement.given,
+            vec![(0, Item::METAL, 15), (1, Item::METAL, 10)]
+        );
+        assert_eq!(settlement.left_behind, vec![(Item::METAL, 5)]);
+    }
+
+    #[test]
+    fn deposits_come_back_on_delivery_only() {
+        let locomotive = inventory(&[(Item::GLASS, 5)]);
+        let mut delivered = contract((Item::GLASS, 5), (Item::WOOD, 10), 1);
+        delivered.deposit = 4;
+        let mut failed = contract((Item::GLASS, 5), (Item::WOOD, 10), 1);
+        failed.deposit = 6;
+
+        let settlement = settle_contracts(&[delivered, failed], 1, 0, &[hold(&locomotive)]);
+
+        assert_eq!(
+            settlement.given,
+            vec![(0, Item::WOOD, 10), (0, Item::MONEY, 4)]
+        );
+        assert_eq!(settlement.failed.len(), 1);
+    }
+
+    #[test]
+    fn contracts_for_another_town_fail_even_with_the_goods() {
+        let locomotive = inventory(&[(Item::COAL, 40)]);
+        let mut elsewhere = contract((Item::COAL, 10), (Item::MONEY, 20), 2);
+        elsewhere.destination = Some(Destination {
+            id: 7,
+            name: "Snodsbury".into(),
+        });
+        let mut here = elsewhere.clone();
+        here.destination = Some(Destination {
+            id: 3,
+            name: "Bellchester".into(),
+        });
+
+        let settlement = settle_contracts(&[elsewhere, here], 2, 3, &[hold(&locomotive)]);
+
+        assert_eq!(settlement.failed[0].1, Failure::WrongTown);
+        assert_eq!(settlement.delivered.len(), 1);
+        assert_eq!(settlement.taken, vec![(0, Item::COAL, 10)]);
+    }
+}
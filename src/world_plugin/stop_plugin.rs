@@ -12,21 +12,41 @@ use bevy::{
     state::commands,
 };
 use rand::{Rng, seq::IndexedRandom};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     FontAssets, GameState, ImageAssets, InGameState,
     control_panel_plugin::AdvanceBlocker,
-    resources_plugin::{Inventory, Item},
-    train_plugin::TrainState,
+    resources_plugin::{
+        AcceptedItems, Capacity, Inventory, Item, ItemRegistry, Items, can_afford, spawn_items,
+        take_items, total_owned,
+    },
+    train_plugin::{
+        AddCarEvent, Locomotive, RemoveCarEvent, Train, TrainState, TrainStats, car_type::CarType,
+    },
     ui_state::InMenu,
     world_plugin::{self, NextStop},
 };
 
-use super::{CurrentStop, GameWorld, NumberedStop, Stop};
+use super::{
+    CurrentStop, GameWorld, NumberedStop, Stop,
+    difficulty::Difficulty,
+    market::{Market, spawn_market_panel},
+    reputation::{Faction, Reputation},
+    route_map::RouteMap,
+    settlement::{Hold, settle_contracts},
+};
 pub fn stop_plugin(app: &mut App) {
     app.add_systems(OnEnter(GameState::InGame), spawn_stop_menu)
         .insert_resource(ActiveContracts(Vec::new()))
         .insert_resource(FadeTime { time: 0. })
+        .add_systems(
+            OnExit(GameState::InGame),
+            |mut contracts: ResMut<ActiveContracts>, mut fade_time: ResMut<FadeTime>| {
+                contracts.0.clear();
+                fade_time.time = 0.;
+            },
+        )
         .add_systems(
             OnEnter(InMenu::StopMenu),
             |mut menu: Single<&mut Visibility, With<StopMenu>>| {
@@ -44,7 +64,7 @@ pub fn stop_plugin(app: &mut App) {
             (
                 show_stop_menu
                     .run_if(resource_exists::<CurrentStop>.and(resource_changed::<CurrentStop>)),
-                hide_stop_menu.run_if(
+                (hide_stop_menu, car_buttons, update_car_buttons, switch_tabs).run_if(
                     in_state(GameState::InGame)
                         .and(in_state(InGameState::Running))
                         .and(in_state(InMenu::StopMenu)),
@@ -66,47 +86,98 @@ pub fn stop_plugin(app: &mut App) {
 #[derive(Resource)]
 pub struct ActiveContracts(pub Vec<Contract>);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Contract {
     pub required: (Item, usize),
     pub reward: (Item, usize),
     pub stop_number: usize,
+    /// The town the goods have to be brought to. Contracts without one can be handed in at
+    /// whichever town ends up with the stop number.
+    pub destination: Option<Destination>,
+    pub issuer: Option<Faction>,
+    /// [`Item::MONEY`] paid up front when signing, handed back on delivery and kept by the
+    /// issuer otherwise.
+    pub deposit: usize,
+    /// Required goods handed over ahead of the deadline. They count towards delivery and can't
+    /// be taken back, so they are lost with the contract if it fails.
+    pub handed_over: usize,
+}
+
+/// A town on the [`RouteMap`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Destination {
+    pub id: usize,
+    pub name: String,
 }
 impl Contract {
-    fn generate_random(rng: &mut impl Rng, current_stop_number: usize) -> Self {
-        let variants = [
-            (Item::Food, 1),
-            (Item::Water, 1),
-            (Item::Wood, 1),
-            (Item::Clay, 1),
-            (Item::Brick, 1),
-            (Item::Metal, 1),
-            (Item::Glass, 1),
-            (Item::Bullet, 1),
-            (Item::Money, 1),
-        ];
-        let required = variants
-            .choose_weighted(rng, |(_, w)| *w)
-            .unwrap()
-            .0
-            .clone();
+    /// How many more of the required goods have to be handed over.
+    pub fn outstanding(&self) -> usize {
+        self.required.1.saturating_sub(self.handed_over)
+    }
+
+    fn generate_random(
+        rng: &mut impl Rng,
+        difficulty: Difficulty,
+        route: &RouteMap,
+        current_stop_number: usize,
+        distance: f32,
+        issuer: Faction,
+        reputation: &Reputation,
+        items: &ItemRegistry,
+    ) -> Self {
+        let level = difficulty.level(current_stop_number, distance);
+        let standing = reputation.get(issuer).max(0) as f32;
+        let variants = items.iter().collect::<Vec<_>>();
+        let required = variants.choose(rng).unwrap().id.clone();
+        // factions that think well of the train offer it the more valuable goods
         let reward = variants
-            .choose_weighted(rng, |(_, w)| *w)
+            .choose_weighted(rng, |it| 1.0 + it.base_price * standing / 20.0)
             .unwrap()
-            .0
+            .id
             .clone();
-        let required_amount = rng.random_range(15..100);
-        let multiplier = ((required_amount as f32) / 10.0).max(1.2).sqrt();
+        let required_amount = rng.random_range(Difficulty::contract_amount(level));
+        let multiplier = ((required_amount as f32) / 10.0).max(1.2).sqrt()
+            * difficulty.reward_factor(level)
+            * reputation.reward_factor(issuer);
+        let deadline = rng.random_range(difficulty.deadline(level)) as isize
+            + reputation.deadline_slack(issuer);
+        let stop_number = current_stop_number + deadline.max(2) as usize;
+        let destination = route
+            .towns_numbered(stop_number)
+            .collect::<Vec<_>>()
+            .choose(rng)
+            .map(|it| Destination {
+                id: it.id,
+                name: it.name.clone(),
+            });
+        let reward_amount = (required_amount as f32 * multiplier) as usize;
+        let deposit =
+            (reward_amount as f32 * items.base_price(&reward) * reputation.deposit_share(issuer))
+                .round() as usize;
         Contract {
             required: (required, required_amount),
-            reward: (reward, (required_amount as f32 * multiplier) as usize),
-            stop_number: current_stop_number + rng.random_range(2..=6),
+            reward: (reward, reward_amount),
+            stop_number,
+            destination,
+            issuer: Some(issuer),
+            deposit,
+            handed_over: 0,
         }
     }
 }
 
 #[derive(Component)]
 struct StopMenu;
+/// Marks which parts of the stop menu belong to which tab, and the buttons that switch to it.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum StopMenuTab {
+    Contracts,
+    Market,
+}
+#[derive(Component)]
+struct TabButton(StopMenuTab);
+#[derive(Component)]
+struct ReputationDisplay;
 #[derive(Component)]
 struct CloseMenuButton;
 
@@ -137,6 +208,7 @@ fn spawn_town_arrival_text(
     println!("arriving at town: {}", town_name);
 
     commands.spawn((
+        StateScoped(GameState::InGame),
         Text::new("Welcome To ".to_string() + &town_name),
         TextFont {
             font: font_assets.town_title_font.clone().into(),
@@ -181,9 +253,10 @@ struct ContractImage;
 const CONTRACT_RATIO: f32 = 149.0 / 99.0;
 const CONTRACT_WIDTH: f32 = 200.0;
 
-fn spawn_stop_menu(mut commands: Commands, image_assets: Res<ImageAssets>) {
+fn spawn_stop_menu(mut commands: Commands, image_assets: Res<ImageAssets>, items: Items) {
     commands
         .spawn((
+            StateScoped(GameState::InGame),
             Node {
                 margin: UiRect::AUTO,
                 display: Display::Flex,
@@ -196,15 +269,44 @@ fn spawn_stop_menu(mut commands: Commands, image_assets: Res<ImageAssets>) {
         ))
         .with_children(|parent| {
             parent
-                .spawn((Node {
-                    width: Val::Px(CONTRACT_WIDTH * 6.),
-                    height: Val::Px(CONTRACT_WIDTH * CONTRACT_RATIO),
+                .spawn(Node {
                     display: Display::Flex,
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Center,
-
+                    column_gap: Val::Px(10.0),
+                    margin: UiRect::bottom(Val::Px(10.0)),
                     ..Default::default()
-                },))
+                })
+                .with_children(|parent| {
+                    for (tab, name) in [
+                        (StopMenuTab::Contracts, "Contracts"),
+                        (StopMenuTab::Market, "Market"),
+                    ] {
+                        parent.spawn((
+                            Node {
+                                width: Val::Px(160.0),
+                                height: Val::Px(20.0),
+                                ..Default::default()
+                            },
+                            BackgroundColor(Color::WHITE),
+                            Button,
+                            TabButton(tab),
+                            children![(Text::new(name), TextColor(Color::BLACK))],
+                        ));
+                    }
+                    parent.spawn((ReputationDisplay, Text::new("")));
+                });
+            parent
+                .spawn((
+                    StopMenuTab::Contracts,
+                    Node {
+                        width: Val::Px(CONTRACT_WIDTH * 6.),
+                        height: Val::Px(CONTRACT_WIDTH * CONTRACT_RATIO),
+                        display: Display::Flex,
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+
+                        ..Default::default()
+                    },
+                ))
                 .with_children(|parent| {
                     for i in 0..6 {
                         parent.spawn((
@@ -219,6 +321,76 @@ fn spawn_stop_menu(mut commands: Commands, image_assets: Res<ImageAssets>) {
                         ));
                     }
                 });
+            parent
+                .spawn((
+                    StopMenuTab::Market,
+                    Node {
+                        width: Val::Px(CONTRACT_WIDTH * 6.),
+                        display: Display::None,
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(10.0)),
+                        row_gap: Val::Px(5.0),
+                        ..Default::default()
+                    },
+                    BackgroundColor(Color::WHITE),
+                ))
+                .with_children(|parent| spawn_market_panel(parent, &items));
+            parent
+                .spawn((
+                    StopMenuTab::Contracts,
+                    Node {
+                        display: Display::Flex,
+                        flex_wrap: FlexWrap::Wrap,
+                        justify_content: JustifyContent::Center,
+                        column_gap: Val::Px(10.0),
+                        row_gap: Val::Px(5.0),
+                        margin: UiRect::vertical(Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                ))
+                .with_children(|parent| {
+                    for car_type in CarType::iterator() {
+                        parent
+                            .spawn((
+                                Node {
+                                    display: Display::Flex,
+                                    align_items: AlignItems::Center,
+                                    column_gap: Val::Px(5.0),
+                                    padding: UiRect::horizontal(Val::Px(5.0)),
+                                    ..Default::default()
+                                },
+                                BackgroundColor(Color::WHITE),
+                                Button,
+                                CarButton::Buy(car_type),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Text::new(format!("Buy {}", car_type.name())),
+                                    TextColor(Color::BLACK),
+                                ));
+                                spawn_items(
+                                    parent,
+                                    &items,
+                                    &car_type.get_cost(),
+                                    TextColor(Color::BLACK),
+                                );
+                                parent.spawn((
+                                    Text::new(format!("{}t", car_type.weight())),
+                                    TextColor(Color::BLACK),
+                                ));
+                            });
+                    }
+                    parent.spawn((
+                        Node {
+                            padding: UiRect::horizontal(Val::Px(5.0)),
+                            ..Default::default()
+                        },
+                        BackgroundColor(Color::WHITE),
+                        Button,
+                        CarButton::Scrap,
+                        children![(Text::new("Scrap Last Car"), TextColor(Color::BLACK))],
+                    ));
+                });
             parent.spawn((
                 Node {
                     width: Val::Px(160.0),
@@ -233,6 +405,72 @@ fn spawn_stop_menu(mut commands: Commands, image_assets: Res<ImageAssets>) {
         });
 }
 
+#[derive(Component)]
+enum CarButton {
+    Buy(CarType),
+    Scrap,
+}
+
+fn car_buttons(
+    interaction_query: Query<(&Interaction, &CarButton), (Changed<Interaction>, With<Button>)>,
+    mut add_car: EventWriter<AddCarEvent>,
+    mut remove_car: EventWriter<RemoveCarEvent>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            match button {
+                CarButton::Buy(car_type) => {
+                    add_car.write(AddCarEvent(*car_type));
+                }
+                CarButton::Scrap => {
+                    remove_car.write(RemoveCarEvent);
+                }
+            }
+        }
+    }
+}
+
+fn update_car_buttons(
+    inventories: Query<&Inventory>,
+    train_stats: Res<TrainStats>,
+    buttons: Query<(Entity, &CarButton)>,
+    children: Query<&Children>,
+    mut text_colors: Query<&mut TextColor>,
+) {
+    for (entity, button) in &buttons {
+        let available = match button {
+            CarButton::Buy(car_type) => can_afford(&inventories, &car_type.get_cost()),
+            CarButton::Scrap => train_stats.length() > 1,
+        };
+        let mut labels = text_colors.iter_many_mut(children.iter_descendants(entity));
+        while let Some(mut text_color) = labels.fetch_next() {
+            text_color.0 = if available {
+                Color::BLACK
+            } else {
+                Color::srgb(0.6, 0.6, 0.6)
+            };
+        }
+    }
+}
+
+fn switch_tabs(
+    interaction_query: Query<(&Interaction, &TabButton), Changed<Interaction>>,
+    mut panels: Query<(&StopMenuTab, &mut Node)>,
+) {
+    for (interaction, TabButton(tab)) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        for (panel, mut node) in &mut panels {
+            node.display = if panel == tab {
+                Display::Flex
+            } else {
+                Display::None
+            };
+        }
+    }
+}
+
 #[derive(Component)]
 struct ContractDisplay;
 
@@ -242,8 +480,13 @@ fn show_stop_menu(
     mut menu_state: ResMut<NextState<InMenu>>,
     mut commands: Commands,
     contracts: Query<Entity, With<ContractImage>>,
-    mut world: ResMut<GameWorld>,
+    world: Res<GameWorld>,
+    route: Res<RouteMap>,
+    reputation: Res<Reputation>,
+    train: Single<&Train>,
     contract_displays: Query<Entity, With<ContractDisplay>>,
+    mut reputation_display: Single<&mut Text, With<ReputationDisplay>>,
+    items: Items,
 ) {
     if let Some(NumberedStop(Stop::Town, current_stop_number)) = current_stop.0 {
         if let Ok(mut menu) = menu.single_mut() {
@@ -257,10 +500,28 @@ fn show_stop_menu(
                     .despawn();
             }
 
+            let issuer = Faction::of(
+                &Market::generate(&world, route.current, &items).produces,
+                &items,
+            );
+            ***reputation_display = format!(
+                "Run by the {}: {} ({:+})",
+                issuer.name(),
+                reputation.standing(issuer),
+                reputation.get(issuer)
+            );
+
+            let mut rng = world.town_rng(route.current);
             for booth in contracts {
                 let contract = Contract::generate_random(
-                    &mut world.rng,
-                    current_stop.0.clone().map(|it| it.1).unwrap_or(0),
+                    &mut rng,
+                    world.difficulty,
+                    &route,
+                    current_stop_number,
+                    train.distance,
+                    issuer,
+                    &reputation,
+                    &items,
                 );
                 commands.entity(booth).with_children(|booth| {
                     booth
@@ -277,32 +538,45 @@ fn show_stop_menu(
 
                                 ..Default::default()
                             },
-                            children![
-                                (
-                                    Text::new(format!(
-                                        "{}x{}",
-                                        contract.required.0.name(),
-                                        contract.required.1
-                                    )),
-                                    TextColor(Color::BLACK)
-                                ),
-                                (Text::new("for"), TextColor(Color::BLACK)),
-                                (
-                                    Text::new(format!(
-                                        "{}x{}",
-                                        contract.reward.0.name(),
-                                        contract.reward.1
-                                    )),
+                        ))
+                        .with_children(|parent| {
+                            let contract_display = parent.target_entity();
+                            spawn_items(
+                                parent,
+                                &items,
+                                std::slice::from_ref(&contract.required),
+                                TextColor(Color::BLACK),
+                            );
+                            parent.spawn((Text::new("for"), TextColor(Color::BLACK)));
+                            spawn_items(
+                                parent,
+                                &items,
+                                std::slice::from_ref(&contract.reward),
+                                TextColor(Color::BLACK),
+                            );
+                            parent.spawn((
+                                    Text::new(match &contract.destination {
+                                        Some(destination) => format!(
+                                            "to {} in {} stops",
+                                            destination.name,
+                                            contract.stop_number - current_stop_number
+                                        ),
+                                        None => format!(
+                                            "in {} stops",
+                                            contract.stop_number - current_stop_number
+                                        ),
+                                    }),
                                     TextColor(Color::BLACK)
-                                ),
-                                (
-                                    Text::new(format!(
-                                        "in {} stops",
-                                        contract.stop_number - current_stop_number
-                                    )),
+                            ));
+                            parent.spawn((
+                                    Text::new(if contract.deposit > 0 {
+                                        format!("Deposit: {} Money", contract.deposit)
+                                    } else {
+                                        String::new()
+                                    }),
                                     TextColor(Color::BLACK)
-                                ),
-                                (
+                            ));
+                            parent.spawn((
                                     Node {
                                         position_type: PositionType::Absolute,
                                         bottom: Val::Percent(30.0),
@@ -311,11 +585,7 @@ fn show_stop_menu(
                                     },
                                     Text::new(format!("X",)),
                                     TextColor(Color::BLACK)
-                                ),
-                            ], // BackgroundColor(Color::WHITE),
-                        ))
-                        .with_children(|parent| {
-                            let contract_display = parent.target_entity();
+                            ));
                             parent
                                 .spawn((
                                     Node {
@@ -341,9 +611,15 @@ fn show_stop_menu(
                                     move |mut trigger: Trigger<Pointer<Pressed>>,
                                      mut commands: Commands,
                                      mut active_contracts: ResMut<ActiveContracts>,
+                                     mut inventories: Query<&mut Inventory>,
                                      image_assets: Res<'_, ImageAssets>,
                                      | {
                                         trigger.propagate(false);
+                                        if total_owned(inventories.iter(), &Item::MONEY) < contract.deposit {
+                                            info!("Can't afford the deposit of {}", contract.deposit);
+                                            return;
+                                        }
+                                        take_items(inventories.iter_mut(), &Item::MONEY, contract.deposit);
 
                                         commands.entity(contract_display).with_child(
                                             (
@@ -426,59 +702,77 @@ fn handle_signature_animation(
     }
 }
 
+/// Hands in the contracts due at this town, see [`settle_contracts`].
 fn evaluate_contracts(
     mut contracts: ResMut<ActiveContracts>,
-    mut inventories: Query<&mut Inventory>,
+    mut inventories: Query<(
+        Entity,
+        &mut Inventory,
+        Option<&AcceptedItems>,
+        Option<&Capacity>,
+        &GlobalTransform,
+        Has<Locomotive>,
+    )>,
     current_stop: Res<CurrentStop>,
+    route: Res<RouteMap>,
+    mut reputation: ResMut<Reputation>,
+    items: Items,
 ) {
+    let Some(NumberedStop(Stop::Town, stop_number)) = current_stop.0 else {
+        return;
+    };
     info!("Number of contracts: {}", contracts.0.len());
-    dbg!(&contracts.0);
-    dbg!(&current_stop.0.is_some());
 
-    let contracts_to_check = contracts.0.iter().enumerate().filter_map(|(i, contract)| {
-        if contract.stop_number == current_stop.0.clone().map(|it| it.1).unwrap_or(0) {
-            Some(i)
-        } else {
-            None
-        }
-    });
-    let mut to_remove = Vec::with_capacity(contracts.0.len());
-    for i in contracts_to_check {
-        let contract = &contracts.0[i];
-        to_remove.push(i);
-        let total_owned = {
-            let mut total = 0;
-            for mut inventory in &mut inventories {
-                total += *inventory
-                    .items
-                    .entry(contract.required.0.clone())
-                    .or_insert(0);
-            }
-            total
-        };
-        let required = contract.required.1;
-        if total_owned < required {
-            info!("Failed contract");
-            continue;
-        }
-        for mut inventory in &mut inventories {
-            let owned = inventory
-                .items
-                .entry(contract.required.0.clone())
-                .or_insert(0);
-            let actual_given = (*owned).min(required);
-            *owned -= actual_given;
-        }
+    // rewards fill the holds front to back with the locomotive last, like stored goods
+    let mut order = inventories
+        .iter()
+        .map(|(entity, .., transform, is_locomotive)| {
+            (entity, (is_locomotive, transform.translation().x))
+        })
+        .collect::<Vec<_>>();
+    order.sort_by(|(_, a), (_, b)| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    let entities = order
+        .into_iter()
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    let settlement = {
+        let holds = entities
+            .iter()
+            .map(|entity| {
+                let (_, inventory, accepted, capacity, ..) = inventories.get(*entity).unwrap();
+                Hold {
+                    inventory,
+                    accepted,
+                    capacity,
+                }
+            })
+            .collect::<Vec<_>>();
+        settle_contracts(&contracts.0, stop_number, route.current, &holds)
+    };
 
-        for mut inventory in &mut inventories {
-            *inventory
-                .items
-                .entry(contract.reward.0.clone())
-                .or_insert(0) += contract.reward.1;
-        }
+    for (hold, item, amount) in &settlement.taken {
+        let (_, mut inventory, ..) = inventories.get_mut(entities[*hold]).unwrap();
+        *inventory.items.get_mut(item).unwrap() -= amount;
+    }
+    for (hold, item, amount) in &settlement.given {
+        let (_, mut inventory, ..) = inventories.get_mut(entities[*hold]).unwrap();
+        *inventory.items.entry(item.clone()).or_insert(0) += amount;
+    }
+    for (item, amount) in &settlement.left_behind {
+        warn!("No room on the train for {amount} {}", items.name(item));
+    }
+
+    for contract in &settlement.delivered {
         info!("Succeeded contract");
+        if let Some(issuer) = contract.issuer {
+            reputation.record(issuer, true);
+        }
     }
-    for i in to_remove {
-        contracts.0.remove(i);
+    for (contract, failure) in &settlement.failed {
+        info!("Failed contract: {failure:?}");
+        if let Some(issuer) = contract.issuer {
+            reputation.record(issuer, false);
+        }
     }
+    contracts.0 = settlement.pending;
 }